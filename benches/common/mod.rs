@@ -0,0 +1,46 @@
+// Synthetic datasets shared by the benchmarks
+use emailspamdetection::tokenize;
+use std::env;
+
+// Emails in the training set unless BENCH_EMAILS says otherwise
+const DEFAULT_EMAILS: usize = 50_000;
+
+const SPAM_WORDS: [&str; 8] = [
+    "free", "win", "prize", "urgent", "lottery", "claim", "offer", "cash",
+];
+const HAM_WORDS: [&str; 8] = [
+    "meeting", "report", "project", "schedule", "invoice", "review", "team", "notes",
+];
+
+// Size of the training set, configurable with e.g. BENCH_EMAILS=200000 cargo bench
+pub fn dataset_size() -> usize {
+    env::var("BENCH_EMAILS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_EMAILS)
+}
+
+// Alternating spam and ham emails of 30 words each
+pub fn synthetic_dataset(rows: usize) -> Vec<(String, String)> {
+    (0..rows)
+        .map(|i| {
+            let (label, words) = if i % 2 == 0 {
+                ("spam", &SPAM_WORDS)
+            } else {
+                ("ham", &HAM_WORDS)
+            };
+            let content: Vec<String> = (0..30)
+                .map(|j| format!("{}{}", words[(i + j) % words.len()], (i * j) % 500))
+                .collect();
+            (label.to_string(), content.join(" "))
+        })
+        .collect()
+}
+
+// Total tokens in the emails, so criterion can report tokens per second
+pub fn token_count(emails: &[(String, String)]) -> u64 {
+    emails
+        .iter()
+        .map(|(_, content)| tokenize(content).len() as u64)
+        .sum()
+}
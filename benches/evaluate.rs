@@ -0,0 +1,46 @@
+// Scoring one validation set several times over, as `train --calibrate
+// --tune-threshold` and `eval` do: tokenizing every pass against tokenizing once
+// with tokenize_batch and reusing the batch
+mod common;
+
+use common::{dataset_size, synthetic_dataset, token_count};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use emailspamdetection::SpamClassifier;
+
+// Emails in the validation set
+const VALIDATION_SIZE: usize = 5000;
+
+fn bench_evaluate(c: &mut Criterion) {
+    let mut classifier = SpamClassifier::builder().ngram(2).build().unwrap();
+    classifier
+        .train(&synthetic_dataset(dataset_size()))
+        .unwrap();
+    let validation: Vec<(String, String)> =
+        synthetic_dataset(dataset_size() + VALIDATION_SIZE).split_off(dataset_size());
+
+    let mut group = c.benchmark_group("evaluate");
+    group.throughput(Throughput::Elements(token_count(&validation)));
+    // calibrate only replaces the fitted parameters, so both variants refit the
+    // same classifier on every iteration
+    group.bench_function("retokenize every pass", |b| {
+        b.iter(|| {
+            classifier.calibrate(&validation).unwrap();
+            black_box(classifier.tune_threshold(&validation));
+            black_box(classifier.evaluate(&validation));
+            black_box(classifier.scored_labels(&validation));
+        })
+    });
+    group.bench_function("tokenize once", |b| {
+        b.iter(|| {
+            let batch = classifier.tokenize_batch(&validation);
+            classifier.calibrate_batch(&batch).unwrap();
+            black_box(classifier.tune_threshold_batch(&batch));
+            black_box(classifier.evaluate_batch(&batch));
+            black_box(classifier.scored_labels_batch(&batch));
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_evaluate);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+// Classification time for a single pathologically long message, such as a whole
+// newsletter pasted into `check`. Scoring must stay linear in message length, so
+// the benchmark first asserts one classification finishes within MAX_DURATION.
+mod common;
+
+use common::{dataset_size, synthetic_dataset, token_count};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use emailspamdetection::{tokenize, SpamClassifier};
+use std::time::{Duration, Instant};
+
+// Words in the long message
+const MESSAGE_WORDS: usize = 50_000;
+
+// Generous bound on one classification, far above the linear-time cost but well
+// below what a quadratic pass over the message would take
+const MAX_DURATION: Duration = Duration::from_secs(2);
+
+// A newsletter-like message mixing training words, unseen words, punctuation,
+// HTML and links
+fn long_message() -> String {
+    let (_, sample) = synthetic_dataset(1).remove(0);
+    let paragraph = format!(
+        "<p>{} Read more at https://example.com/news, it's FREE!</p> <script>x</script>",
+        sample
+    );
+    let paragraphs = MESSAGE_WORDS / tokenize(&paragraph).len();
+    vec![paragraph; paragraphs.max(1)].join("\n")
+}
+
+fn bench_long_message(c: &mut Criterion) {
+    let emails = synthetic_dataset(dataset_size());
+    let mut classifier = SpamClassifier::builder().strip_html(true).build().unwrap();
+    classifier.train(&emails).unwrap();
+    let message = long_message();
+    let tokens = token_count(&[(String::new(), message.clone())]);
+
+    let start = Instant::now();
+    black_box(classifier.classify(&message));
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed < MAX_DURATION,
+        "classifying a {} byte message took {:?}",
+        message.len(),
+        elapsed
+    );
+
+    let mut group = c.benchmark_group("long_message");
+    group.throughput(Throughput::Elements(tokens));
+    group.bench_function(format!("classify {} bytes", message.len()), |b| {
+        b.iter(|| black_box(classifier.classify(&message)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_long_message);
+criterion_main!(benches);
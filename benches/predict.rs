@@ -0,0 +1,41 @@
+// Prediction throughput on a batch of messages, in tokens per second, for a model
+// trained on the synthetic dataset
+mod common;
+
+use common::{dataset_size, synthetic_dataset, token_count};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use emailspamdetection::SpamClassifier;
+
+// Messages classified per iteration
+const BATCH_SIZE: usize = 1000;
+
+fn bench_predict(c: &mut Criterion) {
+    let mut classifier = SpamClassifier::new();
+    classifier
+        .train(&synthetic_dataset(dataset_size()))
+        .unwrap();
+    // Offset so the batch is not simply the start of the training set
+    let batch: Vec<(String, String)> =
+        synthetic_dataset(dataset_size() + BATCH_SIZE).split_off(dataset_size());
+
+    let mut group = c.benchmark_group("predict");
+    group.throughput(Throughput::Elements(token_count(&batch)));
+    group.bench_function(format!("predict {} messages", BATCH_SIZE), |b| {
+        b.iter(|| {
+            for (_, message) in &batch {
+                black_box(classifier.predict(message));
+            }
+        })
+    });
+    group.bench_function(format!("classify {} messages", BATCH_SIZE), |b| {
+        b.iter(|| {
+            for (_, message) in &batch {
+                black_box(classifier.classify(message));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_predict);
+criterion_main!(benches);
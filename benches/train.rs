@@ -0,0 +1,26 @@
+// Training throughput on a large synthetic dataset, in tokens per second. Compare
+// single-threaded and parallel training by running `cargo bench` and
+// `cargo bench --features parallel`.
+mod common;
+
+use common::{dataset_size, synthetic_dataset, token_count};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use emailspamdetection::SpamClassifier;
+
+fn bench_train(c: &mut Criterion) {
+    let emails = synthetic_dataset(dataset_size());
+
+    let mut group = c.benchmark_group("train");
+    group.throughput(Throughput::Elements(token_count(&emails)));
+    group.bench_function(format!("{} emails", emails.len()), |b| {
+        b.iter(|| {
+            let mut classifier = SpamClassifier::new();
+            classifier.train(&emails).unwrap();
+            classifier
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_train);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+// Train from a CSV string and classify without any file IO, the same way the
+// browser build does. Run with `cargo run --example in_memory --features wasm`.
+use emailspamdetection::wasm::WasmClassifier;
+
+const DATASET: &str = "label,content
+spam,Win a free iPhone now! Click here to claim your prize
+spam,URGENT: claim your lottery cash prize today
+ham,Can we move the project meeting to Thursday?
+ham,Please review the attached quarterly report
+";
+
+fn main() {
+    let mut classifier = WasmClassifier::new();
+    if classifier.train_from_str(DATASET).is_err() {
+        eprintln!("The dataset could not be trained on");
+        return;
+    }
+
+    for message in ["Claim your free prize", "Notes from the project meeting"] {
+        println!(
+            "{:.2} {}",
+            classifier.predict_score(message),
+            classifier.classify(message)
+        );
+    }
+}
@@ -0,0 +1,3459 @@
+use crate::data::{dataset_fingerprint, for_each_record, load_data_from_reader, LoadOptions};
+use crate::email::ParsedEmail;
+use crate::error::SpamError;
+use crate::language::detect_language;
+use crate::metrics::{ConfusionMatrix, Metrics, ScoreHistogram};
+use crate::tokenizer::{
+    add_ngrams, char_ngrams, collapse_repeats, contains_phrase, normalize_numbers, soundex,
+    strip_html, structural_tokens, tokenize_with_urls, UrlFeatures,
+};
+use csv::{ReaderBuilder, Writer};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ops::AddAssign;
+use std::path::Path;
+
+// Bumped whenever the serialized model layout changes
+const MODEL_VERSION: u32 = 28;
+
+// Words seen fewer times than this are left out of the top indicator lists
+const MIN_INDICATOR_COUNT: f64 = 2.0;
+
+// Label predict_label returns for messages inside the uncertainty band
+pub const UNCERTAIN_LABEL: &str = "uncertain";
+
+// Number of spam and ham words listed in a ModelSummary
+const SUMMARY_TOP_WORDS: usize = 10;
+
+// Newton iterations fit_platt runs at most before settling for the current fit
+const PLATT_MAX_ITERATIONS: usize = 100;
+
+// Emails counted between two progress callbacks of train_with_progress
+const PROGRESS_INTERVAL: usize = 1000;
+
+// Training detects the language of emails only while the model holds fewer than
+// this many, since detection costs more than tokenizing and counting an email
+const LANGUAGE_SAMPLE: usize = 1000;
+
+// Number of tokens classify reports in Prediction::top_features
+pub(crate) const TOP_FEATURES: usize = 5;
+
+// The keyword list of the original heuristic detector, for use with with_spam_keywords
+pub const DEFAULT_SPAM_KEYWORDS: [&str; 10] = [
+    "free",
+    "win",
+    "urgent",
+    "lottery",
+    "click here",
+    "limited offer",
+    "$$$",
+    "winner",
+    "prize",
+    "congratulations",
+];
+
+// Spacing of the candidate thresholds tried by tune_threshold
+const THRESHOLD_STEP: f64 = 0.01;
+
+// How per-word evidence is combined into a score
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoringMode {
+    // Every token contributes its full log-likelihood
+    #[default]
+    NaiveBayes,
+    // Each token's log-likelihood is weighted by its inverse document frequency,
+    // so words found in most training emails count for less than rare ones
+    TfIdf,
+}
+
+// How often a token is counted per email, in training and in scoring alike
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModelVariant {
+    // Every occurrence counts, so a word repeated throughout a message (or quoted
+    // again and again in a reply chain) weighs that many times
+    #[default]
+    Multinomial,
+    // Each distinct token counts once per email, Bernoulli Naive Bayes style
+    Bernoulli,
+}
+
+// The outcome of SpamClassifier::classify. `label` is "spam" exactly when
+// `is_spam`; `top_features` holds up to five (token, log-likelihood ratio) pairs,
+// strongest first, with positive ratios pointing to spam.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Prediction {
+    pub is_spam: bool,
+    pub score: f64,
+    pub label: String,
+    pub top_features: Vec<(String, f64)>,
+}
+
+// Training statistics returned by SpamClassifier::summary. `priors` holds the
+// share of training emails of every label, sorted by label, and sums to 1.0 for a
+// trained model; the top word lists come from top_spam_words and top_ham_words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSummary {
+    pub spam_count: usize,
+    pub ham_count: usize,
+    pub priors: Vec<(String, f64)>,
+    pub vocabulary_size: usize,
+    pub top_spam_words: Vec<(String, f64)>,
+    pub top_ham_words: Vec<(String, f64)>,
+}
+
+impl fmt::Display for ModelSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Spam emails: {}", self.spam_count)?;
+        writeln!(f, "Ham emails:  {}", self.ham_count)?;
+        for (label, prior) in &self.priors {
+            writeln!(f, "P({}) = {:.3}", label, prior)?;
+        }
+        writeln!(f, "Vocabulary:  {} words", self.vocabulary_size)?;
+        let words = |list: &[(String, f64)]| {
+            list.iter()
+                .map(|(word, ratio)| format!("{} ({:+.1})", word, ratio))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        writeln!(f, "Top spam words: {}", words(&self.top_spam_words))?;
+        write!(f, "Top ham words:  {}", words(&self.top_ham_words))
+    }
+}
+
+// Labeled emails tokenized once by SpamClassifier::tokenize_batch, so the same
+// emails can be scored several times (calibrating, tuning the threshold and
+// evaluating on one validation set) without being tokenized again. A batch only
+// suits classifiers with the tokenization settings of the one that made it.
+pub struct TokenizedBatch {
+    emails: Vec<TokenizedEmail>,
+}
+
+impl TokenizedBatch {
+    pub fn len(&self) -> usize {
+        self.emails.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emails.is_empty()
+    }
+}
+
+struct TokenizedEmail {
+    is_spam: bool,
+    // The whitelist or blacklist verdict, which decides before any scoring
+    list_verdict: Option<bool>,
+    text: TokenizedText,
+}
+
+// What scoring needs from a piece of text: its features and spam keyword count
+struct TokenizedText {
+    tokens: Vec<String>,
+    keyword_matches: usize,
+}
+
+// The learned state of a classifier, taken by SpamClassifier::snapshot so that
+// later updates can be undone with restore. Settings are not part of it.
+pub struct ClassifierSnapshot {
+    counts: TrainingCounts,
+    dataset_fingerprint: Option<String>,
+}
+
+// Simple Naive Bayes Classifier for Spam Detection
+#[derive(Serialize, Deserialize)]
+pub struct SpamClassifier {
+    version: u32,
+    #[serde(flatten)]
+    counts: TrainingCounts,
+    // Laplace smoothing factor added to every word count
+    alpha: f64,
+    // Minimum spam probability for a message to be flagged as spam
+    threshold: f64,
+    // Tokens ignored during both training and prediction
+    stop_words: HashSet<String>,
+    // Words of fewer characters than this are ignored like stop words, except
+    // those listed in keep_short_tokens
+    min_token_len: usize,
+    keep_short_tokens: HashSet<String>,
+    // Longest word n-gram used as a feature; 1 means unigrams only
+    ngram: usize,
+    scoring_mode: ScoringMode,
+    model_variant: ModelVariant,
+    // After training, words seen fewer times than this across both classes are pruned
+    min_word_count: usize,
+    // Remove HTML tags and decode entities before tokenizing
+    strip_html: bool,
+    // Whether URLs are replaced by URL_TOKEN (and optionally their domain)
+    url_features: UrlFeatures,
+    // Add caps-ratio and exclamation tokens so shouting becomes a learned feature
+    structural_features: bool,
+    // Multiplier on the evidence from subject-line tokens of parsed emails
+    subject_weight: f64,
+    // Multiplier on the evidence from the signature of parsed emails, the part of
+    // the body after a "-- " line
+    signature_weight: f64,
+    // Keep the original case of words, so "FREE" and "free" are separate features
+    case_sensitive: bool,
+    // dataset_fingerprint of the data the model was trained on, when recorded
+    dataset_fingerprint: Option<String>,
+    // Length of the character n-grams added alongside word features; 0 disables them.
+    // Each word of L characters adds L + 3 - n grams, so the vocabulary and model
+    // file typically grow several times over.
+    char_ngram: usize,
+    // Width of the band centered on the threshold in which predict_label answers
+    // UNCERTAIN_LABEL instead of a class; 0 disables it
+    uncertainty_band: f64,
+    // Shorten runs of three or more identical letters to two before counting words
+    collapse_repeats: bool,
+    // Replace numbers by NUM_TOKEN and money amounts by MONEY_TOKEN before counting words
+    normalize_numbers: bool,
+    // Replace every word by its Soundex code, so words that sound alike share a
+    // feature. Unrelated words can share a code too, merging their evidence.
+    phonetic: bool,
+    // Lowercase phrases that make any message containing them ham, or spam,
+    // without consulting the model. The whitelist takes precedence.
+    whitelist: Vec<String>,
+    blacklist: Vec<String>,
+    // Lowercase phrases that each add keyword_boost to the spam log-odds of a
+    // message containing them, on top of the model's evidence
+    spam_keywords: Vec<String>,
+    keyword_boost: f64,
+    // Multipliers (spam, ham) on the prior of each class, so errors on the
+    // heavier class cost more; other labels keep a weight of 1.0
+    class_weights: (f64, f64),
+    // Factor in (0.0, 1.0] every existing count is scaled by before update adds
+    // an email, so older emails gradually lose influence; 1.0 never forgets
+    decay: f64,
+    // Platt scaling parameters (a, b) fitted by calibrate: the reported spam
+    // probability is 1 / (1 + e^-(a * raw log-odds + b)). None reports raw scores.
+    calibration: Option<(f64, f64)>,
+}
+
+impl SpamClassifier {
+    pub fn new() -> Self {
+        SpamClassifier {
+            version: MODEL_VERSION,
+            counts: TrainingCounts::default(),
+            alpha: 1.0,
+            threshold: 0.5,
+            stop_words: HashSet::new(),
+            min_token_len: 1,
+            keep_short_tokens: HashSet::new(),
+            ngram: 1,
+            scoring_mode: ScoringMode::default(),
+            model_variant: ModelVariant::default(),
+            min_word_count: 0,
+            strip_html: false,
+            url_features: UrlFeatures::default(),
+            structural_features: false,
+            subject_weight: 1.0,
+            signature_weight: 1.0,
+            case_sensitive: false,
+            dataset_fingerprint: None,
+            char_ngram: 0,
+            uncertainty_band: 0.0,
+            collapse_repeats: false,
+            normalize_numbers: false,
+            phonetic: false,
+            whitelist: Vec::new(),
+            blacklist: Vec::new(),
+            spam_keywords: Vec::new(),
+            keyword_boost: 1.0,
+            class_weights: (1.0, 1.0),
+            decay: 1.0,
+            calibration: None,
+        }
+    }
+
+    // Start configuring a classifier setting by setting; see SpamClassifierBuilder
+    pub fn builder() -> SpamClassifierBuilder {
+        SpamClassifierBuilder::default()
+    }
+
+    // A classifier with Laplace smoothing `alpha`; fails unless alpha is positive
+    pub fn with_alpha(alpha: f64) -> Result<Self, SpamError> {
+        SpamClassifier::builder().alpha(alpha).build()
+    }
+
+    pub fn with_stop_words(mut self, stop_words: HashSet<String>) -> Self {
+        self.stop_words = stop_words;
+        self
+    }
+
+    pub fn with_min_token_len(mut self, min_token_len: usize) -> Self {
+        self.min_token_len = min_token_len;
+        self
+    }
+
+    // Short tokens such as "$" that min_token_len should not drop
+    pub fn with_keep_short_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.keep_short_tokens = tokens.into_iter().collect();
+        self
+    }
+
+    pub fn with_ngram(mut self, ngram: usize) -> Self {
+        self.ngram = ngram.max(1);
+        self
+    }
+
+    pub fn with_scoring_mode(mut self, scoring_mode: ScoringMode) -> Self {
+        self.scoring_mode = scoring_mode;
+        self
+    }
+
+    pub fn with_model_variant(mut self, model_variant: ModelVariant) -> Self {
+        self.model_variant = model_variant;
+        self
+    }
+
+    pub fn with_min_word_count(mut self, min_word_count: usize) -> Self {
+        self.min_word_count = min_word_count;
+        self
+    }
+
+    pub fn with_strip_html(mut self, strip_html: bool) -> Self {
+        self.strip_html = strip_html;
+        self
+    }
+
+    pub fn with_url_features(mut self, url_features: UrlFeatures) -> Self {
+        self.url_features = url_features;
+        self
+    }
+
+    pub fn with_structural_features(mut self, structural_features: bool) -> Self {
+        self.structural_features = structural_features;
+        self
+    }
+
+    pub fn with_subject_weight(mut self, subject_weight: f64) -> Self {
+        self.subject_weight = subject_weight;
+        self
+    }
+
+    pub fn with_signature_weight(mut self, signature_weight: f64) -> Self {
+        self.signature_weight = signature_weight;
+        self
+    }
+
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    pub fn with_char_ngram(mut self, char_ngram: usize) -> Self {
+        self.char_ngram = char_ngram;
+        self
+    }
+
+    pub fn with_uncertainty_band(mut self, uncertainty_band: f64) -> Self {
+        self.uncertainty_band = uncertainty_band;
+        self
+    }
+
+    pub fn with_collapse_repeats(mut self, collapse_repeats: bool) -> Self {
+        self.collapse_repeats = collapse_repeats;
+        self
+    }
+
+    pub fn with_normalize_numbers(mut self, normalize_numbers: bool) -> Self {
+        self.normalize_numbers = normalize_numbers;
+        self
+    }
+
+    pub fn with_phonetic(mut self, phonetic: bool) -> Self {
+        self.phonetic = phonetic;
+        self
+    }
+
+    // Phrases (matched case-insensitively anywhere in a message) that always mean ham
+    pub fn with_whitelist(mut self, phrases: Vec<String>) -> Self {
+        self.whitelist = phrases.iter().map(|p| p.to_lowercase()).collect();
+        self
+    }
+
+    // Phrases (matched case-insensitively anywhere in a message) that always mean
+    // spam, unless the message also matches the whitelist
+    pub fn with_blacklist(mut self, phrases: Vec<String>) -> Self {
+        self.blacklist = phrases.iter().map(|p| p.to_lowercase()).collect();
+        self
+    }
+
+    // Phrases (matched case-insensitively as whole words) that each push the score
+    // towards spam by keyword_boost
+    pub fn with_spam_keywords(mut self, keywords: Vec<String>) -> Self {
+        self.spam_keywords = Vec::new();
+        for keyword in keywords {
+            self.add_spam_keyword(&keyword);
+        }
+        self
+    }
+
+    // Log-odds added to the spam class for every spam keyword a message contains
+    pub fn with_keyword_boost(mut self, keyword_boost: f64) -> Self {
+        self.keyword_boost = keyword_boost;
+        self
+    }
+
+    // Weights (spam, ham) that scale the class priors in the posterior. Raising
+    // the spam weight flags more messages as spam, trading precision for recall
+    // without resampling the training data.
+    pub fn with_class_weights(mut self, class_weights: (f64, f64)) -> Self {
+        self.class_weights = class_weights;
+        self
+    }
+
+    pub fn class_weights(&self) -> (f64, f64) {
+        self.class_weights
+    }
+
+    // Forget old emails exponentially during online learning: each update first
+    // scales every count by `decay`, so an email added n updates ago weighs
+    // decay^n. Batch training is unaffected.
+    pub fn with_decay(mut self, decay: f64) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    pub fn spam_keywords(&self) -> &[String] {
+        &self.spam_keywords
+    }
+
+    // Add a spam keyword; false if it is blank or already in the list
+    pub fn add_spam_keyword(&mut self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        if keyword.is_empty() || self.spam_keywords.contains(&keyword) {
+            return false;
+        }
+        self.spam_keywords.push(keyword);
+        true
+    }
+
+    // Remove a spam keyword; false if it was not in the list
+    pub fn remove_spam_keyword(&mut self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        let before = self.spam_keywords.len();
+        self.spam_keywords.retain(|k| *k != keyword);
+        self.spam_keywords.len() < before
+    }
+
+    // Number of emails the model has been trained on
+    pub fn training_emails(&self) -> usize {
+        self.counts.total_emails().round() as usize
+    }
+
+    pub fn dataset_fingerprint(&self) -> Option<&str> {
+        self.dataset_fingerprint.as_deref()
+    }
+
+    // Record which dataset the model was trained on; saved along with the model
+    pub fn set_dataset_fingerprint(&mut self, fingerprint: String) {
+        self.dataset_fingerprint = Some(fingerprint);
+    }
+
+    // Whether the model records a fingerprint that differs from `emails`. Models
+    // without a recorded fingerprint are never considered stale.
+    pub fn is_stale(&self, emails: &[(String, String)]) -> bool {
+        self.dataset_fingerprint
+            .as_deref()
+            .is_some_and(|fingerprint| fingerprint != dataset_fingerprint(emails))
+    }
+
+    // The language most sampled training emails were reliably detected as, e.g. "eng"
+    pub fn dominant_language(&self) -> Option<&str> {
+        self.counts.dominant_language()
+    }
+
+    // The detected language of a message when it differs from the training
+    // corpus's dominant language; scores for such messages are unreliable
+    pub fn language_mismatch(&self, message: &str) -> Option<&'static str> {
+        let dominant = self.dominant_language()?;
+        detect_language(message).filter(|language| *language != dominant)
+    }
+
+    // Every class label seen in training, sorted
+    pub fn labels(&self) -> Vec<&str> {
+        self.counts.labels()
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn set_threshold(&mut self, threshold: f64) -> Result<(), SpamError> {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(SpamError::InvalidSetting(format!(
+                "threshold must be between 0.0 and 1.0, got {}",
+                threshold
+            )));
+        }
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    // Train on labeled emails. Fails without changing the model when the emails
+    // are empty, or when they and any earlier training lack spam or ham examples.
+    pub fn train(&mut self, emails: &[(String, String)]) -> Result<(), SpamError> {
+        self.check_classes(emails.iter().map(|(label, _)| label.as_str()))?;
+        self.count_emails(emails);
+        self.finish_training();
+        Ok(())
+    }
+
+    // Like train, but calls progress(done, total) after every PROGRESS_INTERVAL
+    // emails and once more when the last email has been counted
+    pub fn train_with_progress(
+        &mut self,
+        emails: &[(String, String)],
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<(), SpamError> {
+        self.check_classes(emails.iter().map(|(label, _)| label.as_str()))?;
+
+        let mut done = 0;
+        for chunk in emails.chunks(PROGRESS_INTERVAL) {
+            self.count_emails(chunk);
+            done += chunk.len();
+            progress(done, emails.len());
+        }
+
+        self.finish_training();
+        Ok(())
+    }
+
+    // Train from a CSV dataset read one record at a time, so the whole dataset
+    // never has to fit in memory. The counts, and so the model, are identical to
+    // load_data followed by train. Returns the number of emails trained on.
+    pub fn train_streaming(&mut self, path: &Path) -> Result<usize, SpamError> {
+        let mut counts = TrainingCounts::default();
+        let sample = self.counts.language_sample_left();
+        let trained = for_each_record(path, &LoadOptions::default(), |label, content| {
+            if counts.total_emails() < sample as f64 {
+                counts.add_language(content);
+            }
+            counts.add(label, self.tokens(content));
+            Ok(())
+        })?;
+
+        self.check_classes(counts.labels().into_iter())?;
+        self.counts.merge(counts);
+        self.finish_training();
+        Ok(trained)
+    }
+
+    // Train on a CSV dataset held in a string, with the same label,content layout
+    // as the files load_data reads
+    pub fn train_from_csv_str(&mut self, csv: &str) -> Result<(), SpamError> {
+        let emails = load_data_from_reader(csv.as_bytes())?;
+        self.train(&emails)
+    }
+
+    // Count a batch of emails the way update counts one, through count or its
+    // per-thread equivalent count_batch, but without decay: every email of a
+    // batch weighs the same
+    fn count_emails(&mut self, emails: &[(String, String)]) {
+        #[cfg(not(feature = "parallel"))]
+        for (label, content) in emails {
+            self.count(label, content);
+        }
+
+        #[cfg(feature = "parallel")]
+        {
+            // Sample the same emails as counting one by one would
+            let sample = self.counts.language_sample_left();
+            for (_, content) in emails.iter().take(sample) {
+                self.counts.add_language(content);
+            }
+            let counts = self.count_batch(emails);
+            self.counts.merge(counts);
+        }
+    }
+
+    fn finish_training(&mut self) {
+        if self.min_word_count > 1 {
+            self.prune(self.min_word_count);
+        }
+    }
+
+    // Check that a training batch is non-empty and that, together with the
+    // existing counts, at least two classes are represented
+    fn check_classes<'a>(&self, labels: impl Iterator<Item = &'a str>) -> Result<(), SpamError> {
+        let mut seen: HashSet<&str> = self.counts.labels().into_iter().collect();
+        let mut any = false;
+        for label in labels {
+            any = true;
+            seen.insert(label);
+        }
+
+        if !any {
+            Err(SpamError::EmptyDataset)
+        } else if seen.len() < 2 {
+            let missing = if seen.contains("spam") { "ham" } else { "spam" };
+            Err(SpamError::MissingClass(missing.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Drop every word seen fewer than min_count times across all classes,
+    // removing its occurrences from the per-class word totals as well
+    pub fn prune(&mut self, min_count: usize) {
+        self.counts.prune(min_count);
+    }
+
+    // Keep only the max_words most frequent words. Counts of every other word are
+    // folded into a shared unknown bucket, and from then on any out-of-vocabulary
+    // word in a message is scored as if it were that bucket. Class totals are unchanged.
+    pub fn limit_vocab(&mut self, max_words: usize) {
+        self.counts.limit_vocab(max_words);
+    }
+
+    // Keep only the k words most associated with the class labels by their
+    // chi-square statistic, dropping the rest like prune does. Words spread evenly
+    // across the classes score near zero and go first.
+    pub fn select_features(&mut self, k: usize) {
+        self.counts.select_features(k);
+    }
+
+    // Fold one labeled email into the existing counts without retraining. Batch
+    // training counts its emails the same way; only update applies decay, which
+    // below 1.0 scales the existing counts down first, touching every count and
+    // so costing time in proportion to the vocabulary.
+    pub fn update(&mut self, label: &str, content: &str) {
+        if self.decay < 1.0 {
+            self.counts.decay(self.decay);
+        }
+        self.count(label, content);
+    }
+
+    // Forget everything learned from training, including the dataset fingerprint
+    // and Platt calibration, leaving an untrained model with the same settings
+    pub fn clear(&mut self) {
+        self.counts = TrainingCounts::default();
+        self.dataset_fingerprint = None;
+        self.calibration = None;
+    }
+
+    // Copy the learned counts so that later updates can be undone with restore.
+    // This clones every count map, so a snapshot takes about as much memory as
+    // the model's vocabulary; for very large models recording the updates made
+    // since and subtracting them again would be cheaper.
+    pub fn snapshot(&self) -> ClassifierSnapshot {
+        ClassifierSnapshot {
+            counts: self.counts.clone(),
+            dataset_fingerprint: self.dataset_fingerprint.clone(),
+        }
+    }
+
+    // Return the learned counts to those of the snapshot, discarding everything
+    // trained since it was taken. Settings changed since then are kept.
+    pub fn restore(&mut self, snapshot: ClassifierSnapshot) {
+        self.counts = snapshot.counts;
+        self.dataset_fingerprint = snapshot.dataset_fingerprint;
+    }
+
+    fn count(&mut self, label: &str, content: &str) {
+        if self.counts.language_sample_left() > 0 {
+            self.counts.add_language(content);
+        }
+        let words = self.tokens(content);
+        self.counts.add(label, words);
+    }
+
+    // Add the counts of another trained model to this one, as if this model had
+    // also been trained on the other model's emails. Both models must turn text
+    // into the same features, so every tokenizer setting has to match; scoring
+    // settings such as alpha and the threshold stay this model's own. Words the
+    // other model pruned stay missing, so models pruned by min_word_count only
+    // approximate training on the union of both datasets.
+    pub fn merge(&mut self, other: &SpamClassifier) -> Result<(), SpamError> {
+        let mismatch = [
+            ("ngram", self.ngram != other.ngram),
+            ("char_ngram", self.char_ngram != other.char_ngram),
+            (
+                "case_sensitive",
+                self.case_sensitive != other.case_sensitive,
+            ),
+            ("strip_html", self.strip_html != other.strip_html),
+            ("url_features", self.url_features != other.url_features),
+            (
+                "structural_features",
+                self.structural_features != other.structural_features,
+            ),
+            (
+                "collapse_repeats",
+                self.collapse_repeats != other.collapse_repeats,
+            ),
+            (
+                "normalize_numbers",
+                self.normalize_numbers != other.normalize_numbers,
+            ),
+            ("phonetic", self.phonetic != other.phonetic),
+            ("model_variant", self.model_variant != other.model_variant),
+            ("stop_words", self.stop_words != other.stop_words),
+            ("min_token_len", self.min_token_len != other.min_token_len),
+            (
+                "keep_short_tokens",
+                self.keep_short_tokens != other.keep_short_tokens,
+            ),
+        ]
+        .into_iter()
+        .find(|(_, differs)| *differs);
+        if let Some((setting, _)) = mismatch {
+            return Err(SpamError::InvalidSetting(format!(
+                "cannot merge models with different {} settings",
+                setting
+            )));
+        }
+
+        self.counts.merge(other.counts.clone());
+        self.dataset_fingerprint = None;
+        Ok(())
+    }
+
+    // Tokenize and count emails on all cores, then merge the per-thread counts.
+    // Counting is associative, so the result is identical to sequential training.
+    #[cfg(feature = "parallel")]
+    fn count_batch(&self, emails: &[(String, String)]) -> TrainingCounts {
+        emails
+            .par_iter()
+            .fold(TrainingCounts::default, |mut counts, (label, content)| {
+                counts.add(label, self.tokens(content));
+                counts
+            })
+            .reduce(TrainingCounts::default, |mut a, b| {
+                a.merge(b);
+                a
+            })
+    }
+
+    // Whether the message is spam. With more than two classes this compares
+    // P(spam) against the threshold, all other classes counting as not spam.
+    // Same as classify(message).is_spam, without working out the top features.
+    // Panics on an untrained model; try_classify reports that as an error instead.
+    pub fn predict(&self, message: &str) -> bool {
+        assert!(self.is_trained(), "predict called on an untrained model");
+        self.is_spam(message, self.predict_score(message))
+    }
+
+    // classify, but failing with SpamError::Untrained when the model has seen no
+    // emails and SpamError::NonFiniteScore when scoring yields NaN or infinity,
+    // rather than returning a meaningless verdict
+    pub fn try_classify(&self, message: &str) -> Result<Prediction, SpamError> {
+        if !self.is_trained() {
+            return Err(SpamError::Untrained);
+        }
+        let prediction = self.classify(message);
+        if !prediction.score.is_finite() {
+            return Err(SpamError::NonFiniteScore(prediction.score));
+        }
+        Ok(prediction)
+    }
+
+    // Whether the model has been trained on at least one email
+    pub fn is_trained(&self) -> bool {
+        self.counts.total_emails() > 0.0
+    }
+
+    // Everything about a single prediction in one call: the verdict, spam
+    // probability, "spam" or "ham" label and the strongest tokens from explain
+    pub fn classify(&self, message: &str) -> Prediction {
+        let score = self.predict_score(message);
+        let mut top_features = self.explain(message);
+        top_features.truncate(TOP_FEATURES);
+        Prediction {
+            is_spam: self.is_spam(message, score),
+            score,
+            label: self.label_for(message, score).to_string(),
+            top_features,
+        }
+    }
+
+    // Probability in 0.0-1.0 that the message is spam; exactly 0.0 for whitelisted
+    // and 1.0 for blacklisted messages
+    pub fn predict_score(&self, message: &str) -> f64 {
+        match self.list_verdict(message) {
+            Some(true) => 1.0,
+            Some(false) => 0.0,
+            None => self.spam_probability(&self.log_posteriors(message)),
+        }
+    }
+
+    // The whitelist or blacklist verdict for a message, if either list matches
+    fn list_verdict(&self, message: &str) -> Option<bool> {
+        if self.whitelist.is_empty() && self.blacklist.is_empty() {
+            return None;
+        }
+
+        let message = message.to_lowercase();
+        if self.whitelist.iter().any(|phrase| message.contains(phrase)) {
+            Some(false)
+        } else if self.blacklist.iter().any(|phrase| message.contains(phrase)) {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    // Number of spam keywords found in a text as whole words, so "win" matches
+    // "win a prize" but not "window"
+    fn keyword_matches(&self, text: &str) -> usize {
+        if self.spam_keywords.is_empty() {
+            return 0;
+        }
+        let text = text.to_lowercase();
+        self.spam_keywords
+            .iter()
+            .filter(|keyword| contains_phrase(&text, keyword))
+            .count()
+    }
+
+    // A list match decides outright; otherwise the score is compared to the threshold
+    fn is_spam(&self, message: &str, score: f64) -> bool {
+        self.list_verdict(message).unwrap_or(score > self.threshold)
+    }
+
+    // The class of the message: "spam" when its spam probability is above the
+    // threshold, as in predict, and otherwise the most probable of the other labels
+    // seen in training, or an empty string for an untrained classifier.
+    // UNCERTAIN_LABEL when the spam probability is within half the uncertainty band
+    // of the threshold.
+    pub fn predict_label(&self, message: &str) -> String {
+        if let Some(is_spam) = self.list_verdict(message) {
+            return if is_spam { "spam" } else { "ham" }.to_string();
+        }
+
+        let scores = self.log_posteriors(message);
+        let spam_probability = self.spam_probability(&scores);
+        if (spam_probability - self.threshold).abs() < self.uncertainty_band / 2.0 {
+            return UNCERTAIN_LABEL.to_string();
+        }
+
+        // Spam is decided by the threshold like in predict; the other classes
+        // compete among themselves
+        if spam_probability > self.threshold {
+            return "spam".to_string();
+        }
+        scores
+            .into_iter()
+            .filter(|(label, _)| *label != "spam")
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(label, _)| label.to_string())
+            .unwrap_or_default()
+    }
+
+    // Train on parsed emails; subject and body are counted as one text, and each
+    // sender domain is counted towards the reputation used by predict_score_parsed
+    pub fn train_parsed(&mut self, emails: &[(String, ParsedEmail)]) -> Result<(), SpamError> {
+        self.check_classes(emails.iter().map(|(label, _)| label.as_str()))?;
+        for (label, email) in emails {
+            self.count(label, &email.text());
+            if let Some(domain) = email.sender_domain() {
+                self.counts.add_sender(label, domain);
+            }
+        }
+        self.finish_training();
+        Ok(())
+    }
+
+    pub fn predict_parsed(&self, email: &ParsedEmail) -> bool {
+        self.is_spam(&email.text(), self.predict_score_parsed(email))
+    }
+
+    // Spam probability of a parsed email, with subject tokens weighted by
+    // subject_weight and signature tokens by signature_weight. A sender domain
+    // seen in training adds its reputation as one more piece of evidence; unknown
+    // domains and emails without one add nothing.
+    pub fn predict_score_parsed(&self, email: &ParsedEmail) -> f64 {
+        match self.list_verdict(&email.text()) {
+            Some(true) => return 1.0,
+            Some(false) => return 0.0,
+            None => {}
+        }
+
+        let (body, signature) = email.body_and_signature();
+        let mut scores = self.weighted_log_posteriors(&[
+            (email.subject.as_str(), self.subject_weight),
+            (body, 1.0),
+            (signature, self.signature_weight),
+        ]);
+        if let Some(domain) = email.sender_domain() {
+            self.add_sender_evidence(&mut scores, &domain);
+        }
+        self.spam_probability(&scores)
+    }
+
+    // P(spam) from per-class log-posteriors, calibrated when calibrate has been run
+    fn spam_probability(&self, scores: &[(&str, f64)]) -> f64 {
+        match self.calibration {
+            Some((a, b)) => sigmoid(a * spam_log_odds(scores) + b),
+            None => label_probability(scores, "spam"),
+        }
+    }
+
+    // Fit Platt scaling on held-out emails: a logistic regression from the raw
+    // spam log-odds to the true labels, applied by predict_score from then on so
+    // that a score of 0.9 means roughly 90% of such emails are spam. Emails decided
+    // by the whitelist or blacklist are left out. Needs both spam and other emails.
+    pub fn calibrate(&mut self, validation: &[(String, String)]) -> Result<(), SpamError> {
+        self.calibrate_batch(&self.tokenize_batch(validation))
+    }
+
+    // calibrate on emails already tokenized by tokenize_batch
+    pub fn calibrate_batch(&mut self, validation: &TokenizedBatch) -> Result<(), SpamError> {
+        let points: Vec<(f64, bool)> = validation
+            .emails
+            .iter()
+            .filter(|email| email.list_verdict.is_none())
+            .map(|email| {
+                let log_odds = spam_log_odds(&self.tokenized_log_posteriors(&[(&email.text, 1.0)]));
+                (log_odds, email.is_spam)
+            })
+            .filter(|(log_odds, _)| log_odds.is_finite())
+            .collect();
+
+        if points.is_empty() {
+            return Err(SpamError::EmptyDataset);
+        }
+        for (class, is_spam) in [("spam", true), ("ham", false)] {
+            if !points.iter().any(|&(_, spam)| spam == is_spam) {
+                return Err(SpamError::MissingClass(class.to_string()));
+            }
+        }
+
+        self.calibration = Some(fit_platt(&points));
+        Ok(())
+    }
+
+    // The fitted Platt scaling parameters (a, b), if the model is calibrated
+    pub fn calibration(&self) -> Option<(f64, f64)> {
+        self.calibration
+    }
+
+    // Install Platt parameters, e.g. fitted on another model of the same data,
+    // or remove them with None
+    pub fn set_calibration(&mut self, calibration: Option<(f64, f64)>) {
+        self.calibration = calibration;
+    }
+
+    // Add the smoothed log P(domain|class) of a trained sender domain to each
+    // class's score, treating domains as one categorical feature
+    fn add_sender_evidence(&self, scores: &mut [(&str, f64)], domain: &str) {
+        if !self.counts.knows_sender(domain) {
+            return;
+        }
+        let domains = self.counts.sender_domain_count() as f64;
+        for (label, score) in scores.iter_mut() {
+            let hits = self.counts.sender_count(label, domain);
+            let total = self.counts.sender_total(label);
+            *score += ((hits + self.alpha) / (total + self.alpha * domains)).ln();
+        }
+    }
+
+    // Score every labeled email in `test` and compare against the true label
+    pub fn evaluate(&self, test: &[(String, String)]) -> Metrics {
+        self.confusion_matrix(test).metrics()
+    }
+
+    // evaluate on emails already tokenized by tokenize_batch
+    pub fn evaluate_batch(&self, test: &TokenizedBatch) -> Metrics {
+        self.confusion_matrix_batch(test).metrics()
+    }
+
+    // Tokenize labeled emails once for the *_batch methods, which score them
+    // without tokenizing them again
+    pub fn tokenize_batch(&self, emails: &[(String, String)]) -> TokenizedBatch {
+        TokenizedBatch {
+            emails: emails
+                .iter()
+                .map(|(label, content)| TokenizedEmail {
+                    is_spam: label == "spam",
+                    list_verdict: self.list_verdict(content),
+                    text: self.tokenize_text(content),
+                })
+                .collect(),
+        }
+    }
+
+    // The spam probability of every email in `test`, paired with whether it is
+    // actually spam
+    pub fn scored_labels(&self, test: &[(String, String)]) -> Vec<(f64, bool)> {
+        test.iter()
+            .map(|(label, content)| (self.predict_score(content), label == "spam"))
+            .collect()
+    }
+
+    // How the spam probabilities of the emails are distributed over `bins`
+    // equal-width bins, e.g. to see whether the threshold sits in a sparse region.
+    // Labels are ignored.
+    pub fn score_histogram(&self, emails: &[(String, String)], bins: usize) -> ScoreHistogram {
+        ScoreHistogram::from_scores(
+            emails
+                .iter()
+                .map(|(_, content)| self.predict_score(content)),
+            bins,
+        )
+    }
+
+    // scored_labels for emails already tokenized by tokenize_batch
+    pub fn scored_labels_batch(&self, test: &TokenizedBatch) -> Vec<(f64, bool)> {
+        test.emails
+            .iter()
+            .map(|email| (self.tokenized_score(email), email.is_spam))
+            .collect()
+    }
+
+    // predict_score of a tokenized email
+    fn tokenized_score(&self, email: &TokenizedEmail) -> f64 {
+        match email.list_verdict {
+            Some(true) => 1.0,
+            Some(false) => 0.0,
+            None => self.spam_probability(&self.tokenized_log_posteriors(&[(&email.text, 1.0)])),
+        }
+    }
+
+    // The threshold in 0.0-1.0, in steps of THRESHOLD_STEP, that maximizes F1 on
+    // `validation`. When several thresholds tie, the middle one is picked so the
+    // margin on both sides is as wide as possible. Apply it with set_threshold.
+    pub fn tune_threshold(&self, validation: &[(String, String)]) -> f64 {
+        self.tune_threshold_batch(&self.tokenize_batch(validation))
+    }
+
+    // tune_threshold on emails already tokenized by tokenize_batch
+    pub fn tune_threshold_batch(&self, validation: &TokenizedBatch) -> f64 {
+        let scored = self.scored_labels_batch(validation);
+
+        let steps = (1.0 / THRESHOLD_STEP).round() as usize;
+        let mut best_f1 = f64::MIN;
+        let mut best_thresholds = Vec::new();
+        for step in 0..=steps {
+            let threshold = step as f64 * THRESHOLD_STEP;
+            let mut matrix = ConfusionMatrix::default();
+            for &(score, actual_spam) in &scored {
+                matrix.record(actual_spam, score > threshold);
+            }
+
+            let f1 = matrix.metrics().f1;
+            if f1 > best_f1 {
+                best_f1 = f1;
+                best_thresholds.clear();
+            }
+            if f1 == best_f1 {
+                best_thresholds.push(threshold);
+            }
+        }
+        best_thresholds[best_thresholds.len() / 2]
+    }
+
+    // The emails in `test` that the model gets wrong, as (content, true label,
+    // spam score) in the order they appear
+    pub fn misclassified<'a>(
+        &'a self,
+        test: &'a [(String, String)],
+    ) -> impl Iterator<Item = (&'a str, &'a str, f64)> + 'a {
+        test.iter().filter_map(|(label, content)| {
+            let score = self.predict_score(content);
+            let wrong = self.is_spam(content, score) != (label == "spam");
+            wrong.then_some((content.as_str(), label.as_str(), score))
+        })
+    }
+
+    pub fn confusion_matrix(&self, test: &[(String, String)]) -> ConfusionMatrix {
+        let mut matrix = ConfusionMatrix::default();
+        for (label, content) in test {
+            matrix.record(label == "spam", self.predict(content));
+        }
+        matrix
+    }
+
+    // confusion_matrix for emails already tokenized by tokenize_batch
+    pub fn confusion_matrix_batch(&self, test: &TokenizedBatch) -> ConfusionMatrix {
+        let mut matrix = ConfusionMatrix::default();
+        for email in &test.emails {
+            let score = self.tokenized_score(email);
+            let predicted = email.list_verdict.unwrap_or(score > self.threshold);
+            matrix.record(email.is_spam, predicted);
+        }
+        matrix
+    }
+
+    // Log of P(class) * P(word|class) over every known word, for each class in
+    // label order
+    fn log_posteriors(&self, message: &str) -> Vec<(&str, f64)> {
+        self.weighted_log_posteriors(&[(message, 1.0)])
+    }
+
+    // Log-posteriors for a message made of several parts, each part's word
+    // evidence scaled by its weight
+    fn weighted_log_posteriors(&self, parts: &[(&str, f64)]) -> Vec<(&str, f64)> {
+        let texts: Vec<(TokenizedText, f64)> = parts
+            .iter()
+            .map(|&(text, weight)| (self.tokenize_text(text), weight))
+            .collect();
+        let parts: Vec<(&TokenizedText, f64)> =
+            texts.iter().map(|(text, weight)| (text, *weight)).collect();
+        self.tokenized_log_posteriors(&parts)
+    }
+
+    // weighted_log_posteriors over parts that are already tokenized
+    fn tokenized_log_posteriors(&self, parts: &[(&TokenizedText, f64)]) -> Vec<(&str, f64)> {
+        let total_emails = self.counts.total_emails();
+        let mut scores: Vec<(&str, f64)> = self
+            .counts
+            .labels()
+            .into_iter()
+            .map(|label| {
+                let prior = self.counts.email_count(label) / total_emails;
+                (label, (prior * self.class_weight(label)).ln())
+            })
+            .collect();
+
+        // The smoothed likelihood's denominator depends only on the class, so its
+        // log is taken once per message rather than once per token
+        let vocab_size = self.vocab_size() as f64;
+        let log_denominators: Vec<f64> = scores
+            .iter()
+            .map(|(label, _)| (self.counts.word_total(label) + self.alpha * vocab_size).ln())
+            .collect();
+
+        for &(text, weight) in parts {
+            let boost = self.keyword_boost * text.keyword_matches as f64;
+            if let Some((_, score)) = scores.iter_mut().find(|(label, _)| *label == "spam") {
+                *score += weight * boost;
+            }
+            for word in &text.tokens {
+                let Some(known) = self.word_source(word) else {
+                    continue;
+                };
+                let weight = weight * self.word_weight(word);
+                for ((label, score), log_denominator) in scores.iter_mut().zip(&log_denominators) {
+                    let hits = self.word_hits(label, word, known);
+                    *score += weight * ((hits + self.alpha).ln() - log_denominator);
+                }
+            }
+        }
+
+        scores
+    }
+
+    fn class_weight(&self, label: &str) -> f64 {
+        match label {
+            "spam" => self.class_weights.0,
+            "ham" => self.class_weights.1,
+            _ => 1.0,
+        }
+    }
+
+    fn tokenize_text(&self, text: &str) -> TokenizedText {
+        TokenizedText {
+            tokens: self.tokens(text),
+            keyword_matches: self.keyword_matches(text),
+        }
+    }
+
+    // Whether a word is scored from its own counts (true) or, for an
+    // out-of-vocabulary word, from the bucket left behind by limit_vocab (false).
+    // None when neither exists: such words carry no evidence either way.
+    fn word_source(&self, word: &str) -> Option<bool> {
+        if self.counts.knows(word) {
+            Some(true)
+        } else if self.counts.has_unknown() {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn word_weight(&self, word: &str) -> f64 {
+        match self.scoring_mode {
+            ScoringMode::NaiveBayes => 1.0,
+            ScoringMode::TfIdf => self.idf(word),
+        }
+    }
+
+    // Occurrences of a word under a label, or of the unknown bucket for a word
+    // word_source scores from the bucket
+    fn word_hits(&self, label: &str, word: &str, known: bool) -> f64 {
+        if known {
+            self.counts.word_count(label, word)
+        } else {
+            self.counts.unknown_count(label)
+        }
+    }
+
+    // Unweighted, smoothed log P(word|label)
+    fn word_log_likelihood(&self, label: &str, word: &str, known: bool, vocab_size: f64) -> f64 {
+        let hits = self.word_hits(label, word, known);
+        let total = self.counts.word_total(label);
+        ((hits + self.alpha) / (total + self.alpha * vocab_size)).ln()
+    }
+
+    // Weighted log P(word|spam) and log P(word|ham), or None for words never seen
+    // in training, which carry no evidence either way
+    fn word_log_likelihoods(&self, word: &str, vocab_size: f64) -> Option<(f64, f64)> {
+        let known = self.word_source(word)?;
+        let weight = self.word_weight(word);
+        Some((
+            weight * self.word_log_likelihood("spam", word, known, vocab_size),
+            weight * self.word_log_likelihood("ham", word, known, vocab_size),
+        ))
+    }
+
+    // Each known token of the message with its total log-likelihood ratio toward spam
+    // (positive pushes toward spam), sorted by magnitude. Empty when no token was seen
+    // in training.
+    pub fn explain(&self, message: &str) -> Vec<(String, f64)> {
+        let vocab_size = self.vocab_size() as f64;
+        let mut contributions: HashMap<String, f64> = HashMap::new();
+
+        for word in self.tokens(message) {
+            if let Some((spam_ll, ham_ll)) = self.word_log_likelihoods(&word, vocab_size) {
+                *contributions.entry(word).or_insert(0.0) += spam_ll - ham_ll;
+            }
+        }
+
+        let mut explanation: Vec<(String, f64)> = contributions.into_iter().collect();
+        explanation.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()).then_with(|| a.0.cmp(&b.0)));
+        explanation
+    }
+
+    // Smoothed log-likelihood ratio log P(word|spam) - log P(word|ham) of a single
+    // trained token (positive leans spam), or None when the token was never seen.
+    // The word is normalized the way message text is before it is looked up.
+    pub fn word_spaminess(&self, word: &str) -> Option<f64> {
+        let word = self.normalize_word(word);
+        if self.counts.total_word_count(&word) == 0.0 {
+            return None;
+        }
+
+        let (spam_ll, ham_ll) = self.word_log_likelihoods(&word, self.vocab_size() as f64)?;
+        Some(spam_ll - ham_ll)
+    }
+
+    // The n words that most strongly indicate spam, with their log-likelihood ratio
+    pub fn top_spam_words(&self, n: usize) -> Vec<(String, f64)> {
+        let mut ranked = self.indicator_ratios();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(n);
+        ranked
+    }
+
+    // The n words that most strongly indicate ham, with their log-likelihood ratio
+    // toward spam (so the most ham-like words have the most negative ratio)
+    pub fn top_ham_words(&self, n: usize) -> Vec<(String, f64)> {
+        let mut ranked = self.indicator_ratios();
+        ranked.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(n);
+        ranked
+    }
+
+    // Spam-vs-ham log-likelihood ratio of every word seen at least MIN_INDICATOR_COUNT times
+    fn indicator_ratios(&self) -> Vec<(String, f64)> {
+        let vocab_size = self.vocab_size() as f64;
+        self.counts
+            .vocabulary()
+            .into_iter()
+            .filter(|word| self.counts.total_word_count(word) >= MIN_INDICATOR_COUNT)
+            .filter_map(|word| {
+                let (spam_ll, ham_ll) = self.word_log_likelihoods(word, vocab_size)?;
+                Some((word.clone(), spam_ll - ham_ll))
+            })
+            .collect()
+    }
+
+    // Bag-of-words counts of the message's tokens, limited to the trained vocabulary
+    pub fn feature_vector(&self, message: &str) -> HashMap<String, usize> {
+        let mut features = HashMap::new();
+        for token in self.tokens(message) {
+            if self.counts.total_word_count(&token) > 0.0 {
+                *features.entry(token).or_insert(0) += 1;
+            }
+        }
+        features
+    }
+
+    // Every word in the trained vocabulary in sorted order, so positions are
+    // stable for turning feature_vector output into dense vectors
+    pub fn vocabulary(&self) -> Vec<&str> {
+        let mut words: Vec<&str> = self
+            .counts
+            .vocabulary()
+            .into_iter()
+            .map(String::as_str)
+            .collect();
+        words.sort_unstable();
+        words
+    }
+
+    // Training statistics of the model; print it for a readable report
+    pub fn summary(&self) -> ModelSummary {
+        let total = self.counts.total_emails();
+        ModelSummary {
+            spam_count: self.counts.email_count("spam").round() as usize,
+            ham_count: self.counts.email_count("ham").round() as usize,
+            priors: self
+                .counts
+                .labels()
+                .into_iter()
+                .map(|label| {
+                    let prior = self.counts.email_count(label) / total;
+                    (label.to_string(), prior)
+                })
+                .collect(),
+            vocabulary_size: self.vocab_size(),
+            top_spam_words: self.top_spam_words(SUMMARY_TOP_WORDS),
+            top_ham_words: self.top_ham_words(SUMMARY_TOP_WORDS),
+        }
+    }
+
+    // Whether a token is shorter than min_token_len and not explicitly kept
+    fn is_too_short(&self, token: &str) -> bool {
+        self.min_token_len > 1
+            && token.chars().count() < self.min_token_len
+            && !self.keep_short_tokens.contains(token)
+    }
+
+    // Stop words are lowercase, so case-sensitive tokens are compared lowercased
+    fn is_stop_word(&self, token: &str) -> bool {
+        if self.case_sensitive {
+            self.stop_words.contains(&token.to_lowercase())
+        } else {
+            self.stop_words.contains(token)
+        }
+    }
+
+    // The token a single word becomes in tokens: lowercased unless case-sensitive,
+    // with numbers, repeated letters and sounds normalized as configured. Input that
+    // does not tokenize to exactly one word, such as an n-gram, is only lowercased.
+    fn normalize_word(&self, word: &str) -> String {
+        let mut words = tokenize_with_urls(word, self.url_features, self.case_sensitive);
+        if self.normalize_numbers {
+            words = normalize_numbers(words);
+        }
+        let mut token = match words.pop() {
+            Some(token) if words.is_empty() => token,
+            _ if self.case_sensitive => return word.to_string(),
+            _ => return word.to_lowercase(),
+        };
+        if self.collapse_repeats {
+            token = collapse_repeats(&token);
+        }
+        if self.phonetic {
+            token = soundex(&token).unwrap_or(token);
+        }
+        token
+    }
+
+    // Tokenize text (after optional HTML stripping and URL detection), collapse
+    // repeated letters and normalize numbers if enabled, drop configured stop words
+    // and words shorter than min_token_len, replace words by their phonetic codes
+    // if enabled and add n-gram, character n-gram and structural features; under
+    // ModelVariant::Bernoulli every feature is kept only once
+    fn tokens(&self, text: &str) -> Vec<String> {
+        let text = if self.strip_html {
+            Cow::Owned(strip_html(text))
+        } else {
+            Cow::Borrowed(text)
+        };
+
+        let mut words = tokenize_with_urls(&text, self.url_features, self.case_sensitive);
+        if self.normalize_numbers {
+            words = normalize_numbers(words);
+        }
+        let words: Vec<String> = words
+            .into_iter()
+            .map(|token| {
+                if self.collapse_repeats {
+                    collapse_repeats(&token)
+                } else {
+                    token
+                }
+            })
+            .filter(|token| !self.is_stop_word(token) && !self.is_too_short(token))
+            .collect();
+        let grams = char_ngrams(&words, self.char_ngram);
+        let words = if self.phonetic {
+            words
+                .into_iter()
+                .map(|word| soundex(&word).unwrap_or(word))
+                .collect()
+        } else {
+            words
+        };
+        let mut features = add_ngrams(words, self.ngram);
+        features.extend(grams);
+
+        if self.structural_features {
+            features.extend(structural_tokens(&text));
+        }
+        if self.model_variant == ModelVariant::Bernoulli {
+            let mut seen = HashSet::new();
+            features.retain(|feature| seen.insert(feature.clone()));
+        }
+        features
+    }
+
+    // Smoothed inverse document frequency: ln((1 + N) / (1 + df)) + 1
+    fn idf(&self, word: &str) -> f64 {
+        let total_emails = self.counts.total_emails();
+        let df = self.counts.doc_freq(word);
+        ((1.0 + total_emails) / (1.0 + df)).ln() + 1.0
+    }
+
+    // Number of distinct words seen in any class
+    fn vocab_size(&self) -> usize {
+        self.counts.vocabulary_size()
+    }
+
+    // Classify every message in the input CSV and write content,label,score rows to output.
+    // Returns the number of rows classified.
+    pub fn predict_file(&self, input: &Path, output: &Path) -> Result<usize, SpamError> {
+        let mut wtr = Writer::from_path(output)?;
+        wtr.write_record(["content", "label", "score"])?;
+
+        let classified = for_each_message(input, |content| {
+            let score = self.predict_score(content);
+            let label = self.label_for(content, score);
+            wtr.write_record([content, label, &format!("{:.4}", score)])?;
+            Ok(())
+        })?;
+
+        wtr.flush()?;
+        Ok(classified)
+    }
+
+    // score_histogram over the messages of a CSV file, read like predict_file
+    // reads them, so an unlabeled export of an inbox works
+    pub fn score_histogram_file(
+        &self,
+        input: &Path,
+        bins: usize,
+    ) -> Result<ScoreHistogram, SpamError> {
+        let mut scores = Vec::new();
+        for_each_message(input, |content| {
+            scores.push(self.predict_score(content));
+            Ok(())
+        })?;
+        Ok(ScoreHistogram::from_scores(scores, bins))
+    }
+
+    // Same as predict_file but writes one JSON prediction object per line
+    pub fn predict_file_json(&self, input: &Path, output: &Path) -> Result<usize, SpamError> {
+        let mut wtr = BufWriter::new(File::create(output)?);
+
+        let classified = for_each_message(input, |content| {
+            writeln!(wtr, "{}", self.prediction_json(content))?;
+            Ok(())
+        })?;
+
+        wtr.flush()?;
+        Ok(classified)
+    }
+
+    // A prediction as a JSON object: {"label":"spam","score":0.87,"message":"..."},
+    // plus a "language" code when the message's language could be detected
+    pub fn prediction_json(&self, message: &str) -> String {
+        let score = self.predict_score(message);
+        let prediction = JsonPrediction {
+            label: self.label_for(message, score),
+            score,
+            message,
+            language: detect_language(message),
+        };
+        serde_json::to_string(&prediction).expect("prediction serializes to JSON")
+    }
+
+    fn label_for(&self, message: &str, score: f64) -> &'static str {
+        if self.is_spam(message, score) {
+            "spam"
+        } else {
+            "ham"
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SpamError> {
+        let json = serde_json::to_string(self)?;
+        // Write to a temporary file and rename it into place so a reader or a
+        // concurrent save never observes a half-written model
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)?;
+        log::info!("Model saved to {}", path.display());
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SpamError> {
+        let json = fs::read_to_string(path)?;
+        let value: serde_json::Value = serde_json::from_str(&json)
+            .map_err(|e| SpamError::ModelParse(format!("{} is corrupt: {}", path.display(), e)))?;
+
+        let version = value.get("version").and_then(|v| v.as_u64());
+        if version != Some(MODEL_VERSION as u64) {
+            return Err(SpamError::ModelParse(format!(
+                "{} has incompatible version {:?} (expected {})",
+                path.display(),
+                version,
+                MODEL_VERSION
+            )));
+        }
+
+        let classifier: SpamClassifier = serde_json::from_value(value)
+            .map_err(|e| SpamError::ModelParse(format!("{} is corrupt: {}", path.display(), e)))?;
+
+        let valid_alpha = valid_alpha(classifier.alpha);
+        let valid_boost = classifier.keyword_boost.is_finite() && classifier.keyword_boost >= 0.0;
+        let (spam_weight, ham_weight) = classifier.class_weights;
+        let valid_weights = valid_class_weight(spam_weight) && valid_class_weight(ham_weight);
+        let valid_decay = valid_decay(classifier.decay);
+        let valid_zones = valid_zone_weight(classifier.subject_weight)
+            && valid_zone_weight(classifier.signature_weight);
+        let valid_calibration = classifier
+            .calibration
+            .is_none_or(|(a, b)| a.is_finite() && b.is_finite());
+        if !valid_alpha
+            || !valid_boost
+            || !valid_weights
+            || !valid_decay
+            || !valid_zones
+            || !valid_calibration
+            || !(0.0..=1.0).contains(&classifier.threshold)
+            || !(0.0..=1.0).contains(&classifier.uncertainty_band)
+        {
+            return Err(SpamError::ModelParse(format!(
+                "{} has invalid settings",
+                path.display()
+            )));
+        }
+
+        log::info!("Model loaded from {}", path.display());
+        Ok(classifier)
+    }
+}
+
+// Chainable configuration for an untrained SpamClassifier. Settings left unset
+// keep the SpamClassifier::new() defaults.
+pub struct SpamClassifierBuilder {
+    alpha: f64,
+    threshold: f64,
+    classifier: SpamClassifier,
+}
+
+impl Default for SpamClassifierBuilder {
+    fn default() -> Self {
+        let classifier = SpamClassifier::new();
+        SpamClassifierBuilder {
+            alpha: classifier.alpha,
+            threshold: classifier.threshold,
+            classifier,
+        }
+    }
+}
+
+impl SpamClassifierBuilder {
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn stop_words(mut self, stop_words: HashSet<String>) -> Self {
+        self.classifier = self.classifier.with_stop_words(stop_words);
+        self
+    }
+
+    pub fn min_token_len(mut self, min_token_len: usize) -> Self {
+        self.classifier = self.classifier.with_min_token_len(min_token_len);
+        self
+    }
+
+    pub fn keep_short_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.classifier = self.classifier.with_keep_short_tokens(tokens);
+        self
+    }
+
+    pub fn ngram(mut self, ngram: usize) -> Self {
+        self.classifier = self.classifier.with_ngram(ngram);
+        self
+    }
+
+    pub fn scoring_mode(mut self, scoring_mode: ScoringMode) -> Self {
+        self.classifier = self.classifier.with_scoring_mode(scoring_mode);
+        self
+    }
+
+    pub fn model_variant(mut self, model_variant: ModelVariant) -> Self {
+        self.classifier = self.classifier.with_model_variant(model_variant);
+        self
+    }
+
+    pub fn min_word_count(mut self, min_word_count: usize) -> Self {
+        self.classifier = self.classifier.with_min_word_count(min_word_count);
+        self
+    }
+
+    pub fn strip_html(mut self, strip_html: bool) -> Self {
+        self.classifier = self.classifier.with_strip_html(strip_html);
+        self
+    }
+
+    pub fn url_features(mut self, url_features: UrlFeatures) -> Self {
+        self.classifier = self.classifier.with_url_features(url_features);
+        self
+    }
+
+    pub fn structural_features(mut self, structural_features: bool) -> Self {
+        self.classifier = self
+            .classifier
+            .with_structural_features(structural_features);
+        self
+    }
+
+    pub fn subject_weight(mut self, subject_weight: f64) -> Self {
+        self.classifier = self.classifier.with_subject_weight(subject_weight);
+        self
+    }
+
+    pub fn signature_weight(mut self, signature_weight: f64) -> Self {
+        self.classifier = self.classifier.with_signature_weight(signature_weight);
+        self
+    }
+
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.classifier = self.classifier.with_case_sensitive(case_sensitive);
+        self
+    }
+
+    pub fn char_ngram(mut self, char_ngram: usize) -> Self {
+        self.classifier = self.classifier.with_char_ngram(char_ngram);
+        self
+    }
+
+    pub fn uncertainty_band(mut self, uncertainty_band: f64) -> Self {
+        self.classifier = self.classifier.with_uncertainty_band(uncertainty_band);
+        self
+    }
+
+    pub fn collapse_repeats(mut self, collapse_repeats: bool) -> Self {
+        self.classifier = self.classifier.with_collapse_repeats(collapse_repeats);
+        self
+    }
+
+    pub fn normalize_numbers(mut self, normalize_numbers: bool) -> Self {
+        self.classifier = self.classifier.with_normalize_numbers(normalize_numbers);
+        self
+    }
+
+    pub fn phonetic(mut self, phonetic: bool) -> Self {
+        self.classifier = self.classifier.with_phonetic(phonetic);
+        self
+    }
+
+    pub fn whitelist(mut self, phrases: Vec<String>) -> Self {
+        self.classifier = self.classifier.with_whitelist(phrases);
+        self
+    }
+
+    pub fn blacklist(mut self, phrases: Vec<String>) -> Self {
+        self.classifier = self.classifier.with_blacklist(phrases);
+        self
+    }
+
+    pub fn spam_keywords(mut self, keywords: Vec<String>) -> Self {
+        self.classifier = self.classifier.with_spam_keywords(keywords);
+        self
+    }
+
+    pub fn keyword_boost(mut self, keyword_boost: f64) -> Self {
+        self.classifier = self.classifier.with_keyword_boost(keyword_boost);
+        self
+    }
+
+    pub fn class_weights(mut self, class_weights: (f64, f64)) -> Self {
+        self.classifier = self.classifier.with_class_weights(class_weights);
+        self
+    }
+
+    pub fn decay(mut self, decay: f64) -> Self {
+        self.classifier = self.classifier.with_decay(decay);
+        self
+    }
+
+    // Produce the configured classifier, rejecting an alpha that is not finite and
+    // positive, a negative or non-finite keyword boost or subject or signature
+    // weight, class weights that are not finite and positive, a decay outside
+    // (0.0, 1.0] and a threshold or uncertainty band outside 0.0-1.0
+    pub fn build(self) -> Result<SpamClassifier, SpamError> {
+        if !valid_decay(self.classifier.decay) {
+            return Err(SpamError::InvalidSetting(format!(
+                "decay must be above 0.0 and at most 1.0, got {}",
+                self.classifier.decay
+            )));
+        }
+        let (spam_weight, ham_weight) = self.classifier.class_weights;
+        if !valid_class_weight(spam_weight) || !valid_class_weight(ham_weight) {
+            return Err(SpamError::InvalidSetting(format!(
+                "class weights must be finite positive numbers, got ({}, {})",
+                spam_weight, ham_weight
+            )));
+        }
+        let boost = self.classifier.keyword_boost;
+        if !boost.is_finite() || boost < 0.0 {
+            return Err(SpamError::InvalidSetting(format!(
+                "keyword boost must be a finite non-negative number, got {}",
+                boost
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.classifier.uncertainty_band) {
+            return Err(SpamError::InvalidSetting(format!(
+                "uncertainty band must be between 0.0 and 1.0, got {}",
+                self.classifier.uncertainty_band
+            )));
+        }
+        if !valid_alpha(self.alpha) {
+            return Err(SpamError::InvalidSetting(format!(
+                "alpha must be a finite positive number, got {}",
+                self.alpha
+            )));
+        }
+        for (name, weight) in [
+            ("subject_weight", self.classifier.subject_weight),
+            ("signature_weight", self.classifier.signature_weight),
+        ] {
+            if !valid_zone_weight(weight) {
+                return Err(SpamError::InvalidSetting(format!(
+                    "{} must be a finite non-negative number, got {}",
+                    name, weight
+                )));
+            }
+        }
+
+        let mut classifier = self.classifier;
+        classifier.alpha = self.alpha;
+        classifier.set_threshold(self.threshold)?;
+        Ok(classifier)
+    }
+}
+
+// Without smoothing, a word seen only in one class and a word seen only in another
+// give every class a log-likelihood of -inf, and the score becomes NaN
+fn valid_alpha(alpha: f64) -> bool {
+    alpha.is_finite() && alpha > 0.0
+}
+
+// A zero or negative weight would take the log of a non-positive prior
+fn valid_class_weight(weight: f64) -> bool {
+    weight.is_finite() && weight > 0.0
+}
+
+// A zero weight ignores the subject or signature; a negative one would invert it
+fn valid_zone_weight(weight: f64) -> bool {
+    weight.is_finite() && weight >= 0.0
+}
+
+// A decay of 0.0 would erase everything learned on every update
+fn valid_decay(decay: f64) -> bool {
+    decay > 0.0 && decay <= 1.0
+}
+
+// Normalize per-class log-posteriors into the probability of one label:
+// e^label / sum of e^class, which for two classes is 1 / (1 + e^(other - label)).
+// 0.0 when the label was never trained.
+fn label_probability(scores: &[(&str, f64)], label: &str) -> f64 {
+    let Some(&(_, target)) = scores.iter().find(|(l, _)| *l == label) else {
+        return 0.0;
+    };
+    1.0 / scores
+        .iter()
+        .map(|(_, score)| (score - target).exp())
+        .sum::<f64>()
+}
+
+// log P(spam) - log P(not spam) from per-class log-posteriors: -inf when spam was
+// never trained and +inf when it is the only class
+fn spam_log_odds(scores: &[(&str, f64)]) -> f64 {
+    let Some(&(_, spam)) = scores.iter().find(|(label, _)| *label == "spam") else {
+        return f64::NEG_INFINITY;
+    };
+    let others: Vec<f64> = scores
+        .iter()
+        .filter(|(label, _)| *label != "spam")
+        .map(|(_, score)| *score)
+        .collect();
+    let max = others.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return f64::INFINITY;
+    }
+    let log_sum = max
+        + others
+            .iter()
+            .map(|score| (score - max).exp())
+            .sum::<f64>()
+            .ln();
+    spam - log_sum
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+// Platt's method: fit P(spam) = sigmoid(a * log_odds + b) to (log_odds, is_spam)
+// points by Newton's method with a backtracking line search, against the smoothed
+// targets (N+ + 1) / (N+ + 2) and 1 / (N- + 2) so separable data can't drive the
+// parameters to infinity. Expects both classes to be present.
+fn fit_platt(points: &[(f64, bool)]) -> (f64, f64) {
+    let positives = points.iter().filter(|(_, spam)| *spam).count() as f64;
+    let negatives = points.len() as f64 - positives;
+    let high = (positives + 1.0) / (positives + 2.0);
+    let low = 1.0 / (negatives + 2.0);
+    let targets: Vec<(f64, f64)> = points
+        .iter()
+        .map(|&(z, spam)| (z, if spam { high } else { low }))
+        .collect();
+
+    // Negative log-likelihood, using ln(1 + e^x) - t * x for numerical stability
+    let loss = |a: f64, b: f64| -> f64 {
+        targets
+            .iter()
+            .map(|&(z, t)| {
+                let x = a * z + b;
+                x.max(0.0) + (-x.abs()).exp().ln_1p() - t * x
+            })
+            .sum()
+    };
+
+    let (mut a, mut b) = (0.0, ((positives + 1.0) / (negatives + 1.0)).ln());
+    let mut current = loss(a, b);
+    for _ in 0..PLATT_MAX_ITERATIONS {
+        let (mut g_a, mut g_b, mut h_aa, mut h_ab, mut h_bb) = (0.0, 0.0, 1e-12, 0.0, 1e-12);
+        for &(z, t) in &targets {
+            let p = sigmoid(a * z + b);
+            let w = p * (1.0 - p);
+            g_a += (p - t) * z;
+            g_b += p - t;
+            h_aa += w * z * z;
+            h_ab += w * z;
+            h_bb += w;
+        }
+        if g_a.abs() < 1e-5 && g_b.abs() < 1e-5 {
+            break;
+        }
+
+        let det = h_aa * h_bb - h_ab * h_ab;
+        let step_a = -(h_bb * g_a - h_ab * g_b) / det;
+        let step_b = -(h_aa * g_b - h_ab * g_a) / det;
+        let slope = g_a * step_a + g_b * step_b;
+
+        let mut step = 1.0;
+        while step >= 1e-10 {
+            let candidate = loss(a + step * step_a, b + step * step_b);
+            if candidate < current + 1e-4 * step * slope {
+                a += step * step_a;
+                b += step * step_b;
+                current = candidate;
+                break;
+            }
+            step /= 2.0;
+        }
+        if step < 1e-10 {
+            break;
+        }
+    }
+    (a, b)
+}
+
+// Word and email counts learned from training emails, kept per class label.
+// Counts are whole numbers unless decay has scaled them down.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct TrainingCounts {
+    // Occurrences of each word in the emails of each label
+    word_counts: HashMap<String, HashMap<String, f64>>,
+    // Total number of words seen per label, including repeats
+    word_totals: HashMap<String, f64>,
+    // Number of training emails per label
+    email_counts: HashMap<String, f64>,
+    // Number of training emails each word appears in, for TF-IDF weighting
+    doc_freq: HashMap<String, f64>,
+    // Per-label counts of the words folded away by limit_vocab
+    unknown_counts: HashMap<String, f64>,
+    // Number of sampled training emails reliably detected as each language
+    #[serde(default)]
+    languages: HashMap<String, f64>,
+    // Number of parsed training emails of each label sent from each domain
+    #[serde(default)]
+    sender_domains: HashMap<String, HashMap<String, f64>>,
+}
+
+impl TrainingCounts {
+    fn add(&mut self, label: &str, words: Vec<String>) {
+        let distinct: HashSet<&String> = words.iter().collect();
+        for word in distinct {
+            *self.doc_freq.entry(word.clone()).or_insert(0.0) += 1.0;
+        }
+
+        *self.email_counts.entry(label.to_string()).or_insert(0.0) += 1.0;
+        *self.word_totals.entry(label.to_string()).or_insert(0.0) += words.len() as f64;
+        let word_counts = self.word_counts.entry(label.to_string()).or_default();
+        for word in words {
+            *word_counts.entry(word).or_insert(0.0) += 1.0;
+        }
+    }
+
+    // Scale every count learned from emails by `factor`, so evidence from earlier
+    // emails weighs less than that of emails added afterwards
+    fn decay(&mut self, factor: f64) {
+        let scale = |counts: &mut HashMap<String, f64>| {
+            counts.values_mut().for_each(|count| *count *= factor);
+        };
+        self.word_counts.values_mut().for_each(scale);
+        self.sender_domains.values_mut().for_each(scale);
+        scale(&mut self.word_totals);
+        scale(&mut self.email_counts);
+        scale(&mut self.doc_freq);
+        scale(&mut self.unknown_counts);
+        scale(&mut self.languages);
+    }
+
+    // How many more emails have their language detected: those counted while the
+    // model holds fewer than LANGUAGE_SAMPLE. Decay shrinks the counts, so a
+    // decaying model keeps sampling the language of recent emails.
+    fn language_sample_left(&self) -> usize {
+        (LANGUAGE_SAMPLE as f64 - self.total_emails())
+            .max(0.0)
+            .ceil() as usize
+    }
+
+    fn add_language(&mut self, content: &str) {
+        if let Some(language) = detect_language(content) {
+            *self.languages.entry(language.to_string()).or_insert(0.0) += 1.0;
+        }
+    }
+
+    fn add_sender(&mut self, label: &str, domain: String) {
+        *self
+            .sender_domains
+            .entry(label.to_string())
+            .or_default()
+            .entry(domain)
+            .or_insert(0.0) += 1.0;
+    }
+
+    fn sender_count(&self, label: &str, domain: &str) -> f64 {
+        self.sender_domains
+            .get(label)
+            .and_then(|counts| counts.get(domain))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    // Number of training emails of a label that had a sender domain
+    fn sender_total(&self, label: &str) -> f64 {
+        self.sender_domains
+            .get(label)
+            .map_or(0.0, |counts| counts.values().sum())
+    }
+
+    fn knows_sender(&self, domain: &str) -> bool {
+        self.sender_domains
+            .values()
+            .any(|counts| counts.contains_key(domain))
+    }
+
+    // Number of distinct sender domains across all labels
+    fn sender_domain_count(&self) -> usize {
+        self.sender_domains
+            .values()
+            .flat_map(|counts| counts.keys())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    // Every trained label, sorted so scores come out in a stable order
+    fn labels(&self) -> Vec<&str> {
+        let mut labels: Vec<&str> = self.email_counts.keys().map(String::as_str).collect();
+        labels.sort_unstable();
+        labels
+    }
+
+    fn total_emails(&self) -> f64 {
+        self.email_counts.values().sum()
+    }
+
+    fn email_count(&self, label: &str) -> f64 {
+        *self.email_counts.get(label).unwrap_or(&0.0)
+    }
+
+    fn word_total(&self, label: &str) -> f64 {
+        *self.word_totals.get(label).unwrap_or(&0.0)
+    }
+
+    fn word_count(&self, label: &str, word: &str) -> f64 {
+        self.word_counts
+            .get(label)
+            .and_then(|counts| counts.get(word))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    // Occurrences of a word across all labels
+    fn total_word_count(&self, word: &str) -> f64 {
+        self.word_counts
+            .values()
+            .filter_map(|counts| counts.get(word))
+            .sum()
+    }
+
+    fn doc_freq(&self, word: &str) -> f64 {
+        *self.doc_freq.get(word).unwrap_or(&0.0)
+    }
+
+    fn unknown_count(&self, label: &str) -> f64 {
+        *self.unknown_counts.get(label).unwrap_or(&0.0)
+    }
+
+    fn has_unknown(&self) -> bool {
+        self.unknown_counts.values().any(|&count| count > 0.0)
+    }
+
+    // Every vocabulary word has a document frequency and removing a word removes
+    // both, so these answer in constant time what vocabulary() would by collecting
+    fn vocabulary_size(&self) -> usize {
+        self.doc_freq.len()
+    }
+
+    fn knows(&self, word: &str) -> bool {
+        self.doc_freq.contains_key(word)
+    }
+
+    // Distinct words seen under any label
+    fn vocabulary(&self) -> HashSet<&String> {
+        self.word_counts
+            .values()
+            .flat_map(|counts| counts.keys())
+            .collect()
+    }
+
+    // Remove the given words from every label, returning the removed per-label counts
+    fn remove_words(&mut self, words: &[String]) -> HashMap<String, f64> {
+        let mut removed: HashMap<String, f64> = HashMap::new();
+        for (label, counts) in self.word_counts.iter_mut() {
+            for word in words {
+                if let Some(count) = counts.remove(word) {
+                    *removed.entry(label.clone()).or_insert(0.0) += count;
+                }
+            }
+        }
+        for word in words {
+            self.doc_freq.remove(word);
+        }
+        removed
+    }
+
+    fn prune(&mut self, min_count: usize) {
+        let rare: Vec<String> = self
+            .vocabulary()
+            .into_iter()
+            .filter(|word| self.total_word_count(word) < min_count as f64)
+            .cloned()
+            .collect();
+
+        self.drop_words(&rare);
+    }
+
+    // Remove words along with their occurrences in the per-label word totals
+    fn drop_words(&mut self, words: &[String]) {
+        for (label, count) in self.remove_words(words) {
+            if let Some(total) = self.word_totals.get_mut(&label) {
+                *total -= count;
+            }
+        }
+    }
+
+    fn select_features(&mut self, k: usize) {
+        let mut scored: Vec<(String, f64)> = self
+            .vocabulary()
+            .into_iter()
+            .map(|word| (word.clone(), self.chi_square(word)))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let dropped: Vec<String> = scored.into_iter().skip(k).map(|(word, _)| word).collect();
+        self.drop_words(&dropped);
+    }
+
+    // Chi-square statistic of a word against the labels, over the table of word
+    // occurrences: for each label, this word versus every other word
+    fn chi_square(&self, word: &str) -> f64 {
+        let grand_total: f64 = self.word_totals.values().sum();
+        let word_total = self.total_word_count(word);
+        let other_total = grand_total - word_total;
+        if grand_total == 0.0 || word_total == 0.0 || other_total == 0.0 {
+            return 0.0;
+        }
+
+        let mut chi_square = 0.0;
+        for (label, &label_total) in &self.word_totals {
+            let share = label_total / grand_total;
+            let observed = self.word_count(label, word);
+            let expected = word_total * share;
+            let observed_other = label_total - observed;
+            let expected_other = other_total * share;
+            if expected > 0.0 {
+                chi_square += (observed - expected).powi(2) / expected;
+            }
+            if expected_other > 0.0 {
+                chi_square += (observed_other - expected_other).powi(2) / expected_other;
+            }
+        }
+        chi_square
+    }
+
+    fn limit_vocab(&mut self, max_words: usize) {
+        let mut words: Vec<(String, f64)> = self
+            .vocabulary()
+            .into_iter()
+            .map(|word| (word.clone(), self.total_word_count(word)))
+            .collect();
+        words.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let dropped: Vec<String> = words
+            .into_iter()
+            .skip(max_words)
+            .map(|(word, _)| word)
+            .collect();
+        for (label, count) in self.remove_words(&dropped) {
+            *self.unknown_counts.entry(label).or_insert(0.0) += count;
+        }
+    }
+
+    fn merge(&mut self, other: TrainingCounts) {
+        for (label, counts) in other.word_counts {
+            merge_word_counts(self.word_counts.entry(label).or_default(), counts);
+        }
+        merge_word_counts(&mut self.word_totals, other.word_totals);
+        merge_word_counts(&mut self.email_counts, other.email_counts);
+        merge_word_counts(&mut self.doc_freq, other.doc_freq);
+        merge_word_counts(&mut self.unknown_counts, other.unknown_counts);
+        merge_word_counts(&mut self.languages, other.languages);
+        for (label, counts) in other.sender_domains {
+            merge_word_counts(self.sender_domains.entry(label).or_default(), counts);
+        }
+    }
+
+    // The language most training emails were written in; ties go to the
+    // alphabetically first code so the answer is stable
+    fn dominant_language(&self) -> Option<&str> {
+        self.languages
+            .iter()
+            .max_by(|(a, x), (b, y)| x.total_cmp(y).then(b.cmp(a)))
+            .map(|(language, _)| language.as_str())
+    }
+}
+
+fn merge_word_counts<T: AddAssign + Default>(
+    into: &mut HashMap<String, T>,
+    from: HashMap<String, T>,
+) {
+    for (word, count) in from {
+        *into.entry(word).or_default() += count;
+    }
+}
+
+#[derive(Serialize)]
+struct JsonPrediction<'a> {
+    label: &'a str,
+    score: f64,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
+}
+
+// Feed each message of a CSV to `handle`. A first row with a "content" column is
+// taken as the header and the messages are read from that column; otherwise every
+// row, the first included, is a message in the first column, so a plain list of
+// messages works too. Malformed rows are logged as warnings and skipped. Returns
+// the number of messages handled.
+fn for_each_message<F>(input: &Path, mut handle: F) -> Result<usize, SpamError>
+where
+    F: FnMut(&str) -> Result<(), SpamError>,
+{
+    let mut rdr = ReaderBuilder::new().has_headers(false).from_path(input)?;
+    let mut records = rdr.records().enumerate().peekable();
+    let header_column = match records.peek() {
+        Some((_, Ok(first))) => first
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case("content")),
+        _ => None,
+    };
+    let content_column = match header_column {
+        Some(column) => {
+            records.next();
+            column
+        }
+        None => {
+            log::info!(
+                "{} has no content header; reading messages from the first column",
+                input.display()
+            );
+            0
+        }
+    };
+
+    let mut handled = 0;
+    for (row, result) in records {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                log::warn!("Skipping malformed row {}: {}", row + 1, e);
+                continue;
+            }
+        };
+        let Some(content) = record.get(content_column) else {
+            log::warn!("Skipping row {}: missing message column", row + 1);
+            continue;
+        };
+
+        handle(content)?;
+        handled += 1;
+    }
+
+    Ok(handled)
+}
+
+impl Default for SpamClassifier {
+    fn default() -> Self {
+        SpamClassifier::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    fn dataset(rows: &[(&str, &str)]) -> Vec<(String, String)> {
+        rows.iter()
+            .map(|(label, content)| (label.to_string(), content.to_string()))
+            .collect()
+    }
+
+    fn trained(classifier: SpamClassifier, rows: &[(&str, &str)]) -> SpamClassifier {
+        let mut classifier = classifier;
+        classifier.train(&dataset(rows)).unwrap();
+        classifier
+    }
+
+    const SMALL: [(&str, &str); 4] = [
+        ("spam", "win a free prize now"),
+        ("spam", "claim your free lottery prize"),
+        ("ham", "open the window before the meeting"),
+        ("ham", "the meeting notes are attached"),
+    ];
+
+    const THREE_CLASSES: [(&str, &str); 6] = [
+        ("spam", "win a free prize now"),
+        ("spam", "claim your lottery prize"),
+        ("ham", "meeting notes attached"),
+        ("ham", "lunch with the team tomorrow"),
+        ("promotions", "summer sale 20 percent off shoes"),
+        ("promotions", "new arrivals sale this weekend"),
+    ];
+
+    #[test]
+    fn builder_applies_every_setting() {
+        let stop_words: HashSet<String> = ["the".to_string()].into_iter().collect();
+        let classifier = SpamClassifier::builder()
+            .alpha(0.5)
+            .threshold(0.8)
+            .stop_words(stop_words.clone())
+            .ngram(2)
+            .scoring_mode(ScoringMode::TfIdf)
+            .min_word_count(3)
+            .strip_html(true)
+            .url_features(UrlFeatures::TokenAndDomain)
+            .structural_features(true)
+            .subject_weight(2.0)
+            .case_sensitive(true)
+            .char_ngram(3)
+            .uncertainty_band(0.1)
+            .collapse_repeats(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(classifier.alpha, 0.5);
+        assert_eq!(classifier.threshold(), 0.8);
+        assert_eq!(classifier.stop_words, stop_words);
+        assert_eq!(classifier.ngram, 2);
+        assert_eq!(classifier.scoring_mode, ScoringMode::TfIdf);
+        assert_eq!(classifier.min_word_count, 3);
+        assert!(classifier.strip_html);
+        assert_eq!(classifier.url_features, UrlFeatures::TokenAndDomain);
+        assert!(classifier.structural_features);
+        assert_eq!(classifier.subject_weight, 2.0);
+        assert!(classifier.case_sensitive);
+        assert_eq!(classifier.char_ngram, 3);
+        assert_eq!(classifier.uncertainty_band, 0.1);
+        assert!(classifier.collapse_repeats);
+    }
+
+    #[test]
+    fn zero_alpha_is_rejected() {
+        let error = SpamClassifier::builder().alpha(0.0).build().err().unwrap();
+        assert!(matches!(error, SpamError::InvalidSetting(_)));
+        assert!(SpamClassifier::builder().alpha(-1.0).build().is_err());
+        assert!(SpamClassifier::builder().alpha(f64::NAN).build().is_err());
+        assert!(SpamClassifier::builder().alpha(0.01).build().is_ok());
+        for alpha in [0.0, -1.0, f64::NAN] {
+            assert!(SpamClassifier::with_alpha(alpha).is_err(), "{}", alpha);
+        }
+        assert_eq!(SpamClassifier::with_alpha(0.5).unwrap().alpha, 0.5);
+
+        let path = scratch_dir("zero-alpha").join("model.json");
+        let mut classifier = trained(SpamClassifier::new(), &SMALL);
+        classifier.alpha = 0.0;
+        classifier.save(&path).unwrap();
+        assert!(matches!(
+            SpamClassifier::load(&path),
+            Err(SpamError::ModelParse(_))
+        ));
+    }
+
+    #[test]
+    fn predict_label_picks_the_most_probable_of_three_classes() {
+        let classifier = trained(SpamClassifier::new(), &THREE_CLASSES);
+        assert_eq!(classifier.predict_label("weekend shoes sale"), "promotions");
+        assert_eq!(classifier.predict_label("free lottery prize"), "spam");
+        assert_eq!(classifier.predict_label("team meeting notes"), "ham");
+    }
+
+    #[test]
+    fn predict_label_respects_the_threshold() {
+        let mut classifier = trained(SpamClassifier::new(), &SMALL);
+        let message = "free prize meeting";
+        let score = classifier.predict_score(message);
+        assert!(score > 0.5 && score < 0.99);
+        assert_eq!(classifier.predict_label(message), "spam");
+        classifier.set_threshold(0.99).unwrap();
+        assert!(!classifier.predict(message));
+        assert_eq!(classifier.predict_label(message), "ham");
+    }
+
+    #[test]
+    fn predict_file_reads_plain_lists_and_headed_csvs() {
+        let classifier = trained(SpamClassifier::new(), &SMALL);
+        let dir = scratch_dir("predict-file");
+        let output = dir.join("classified.csv");
+
+        let plain = dir.join("plain.csv");
+        fs::write(&plain, "hello world\nwin free prize\n").unwrap();
+        assert_eq!(classifier.predict_file(&plain, &output).unwrap(), 2);
+
+        let headed = dir.join("headed.csv");
+        fs::write(&headed, "id,content\n1,hello world\n2,win free prize\n").unwrap();
+        assert_eq!(classifier.predict_file(&headed, &output).unwrap(), 2);
+        let written = fs::read_to_string(&output).unwrap();
+        assert!(written.starts_with("content,label,score\nhello world,ham,"));
+        assert!(written.contains("win free prize,spam,"));
+    }
+
+    #[test]
+    fn predict_file_skips_malformed_rows() {
+        let classifier = trained(SpamClassifier::new(), &SMALL);
+        let dir = scratch_dir("predict-file-malformed");
+        let input = dir.join("malformed.csv");
+        fs::write(
+            &input,
+            "content,label\nwin free prize,x\nonly one field\nhello,y\n",
+        )
+        .unwrap();
+        let output = dir.join("classified.csv");
+        assert_eq!(classifier.predict_file(&input, &output).unwrap(), 2);
+    }
+
+    #[test]
+    fn added_spam_keyword_raises_the_score() {
+        let mut classifier = trained(SpamClassifier::new().with_keyword_boost(3.0), &SMALL);
+        let message = "lottery tickets for the meeting";
+        let before = classifier.predict_score(message);
+        assert!(classifier.add_spam_keyword("Tickets"));
+        assert!(classifier.predict_score(message) > before);
+        assert!(classifier.remove_spam_keyword("tickets"));
+        assert_eq!(classifier.predict_score(message), before);
+    }
+
+    #[test]
+    fn spam_keywords_match_whole_words_only() {
+        let mut classifier = trained(SpamClassifier::new().with_keyword_boost(3.0), &SMALL);
+        let message = "open the window meeting";
+        let before = classifier.predict_score(message);
+        classifier.add_spam_keyword("win");
+        assert_eq!(classifier.predict_score(message), before);
+        assert!(
+            classifier.predict_score("you win, open it") > classifier.predict_score("you open it")
+        );
+    }
+
+    fn parsed(subject: &str, body: &str) -> ParsedEmail {
+        ParsedEmail {
+            subject: subject.to_string(),
+            from: String::new(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn subject_weight_raises_a_spammy_subject() {
+        let email = parsed("free lottery prize", "the meeting notes are attached");
+        let plain = trained(SpamClassifier::new(), &SMALL);
+        let weighted = trained(
+            SpamClassifier::builder()
+                .subject_weight(3.0)
+                .build()
+                .unwrap(),
+            &SMALL,
+        );
+        assert!(weighted.predict_score_parsed(&email) > plain.predict_score_parsed(&email));
+        assert!(weighted.predict_parsed(&email));
+    }
+
+    #[test]
+    fn train_parsed_honors_min_word_count() {
+        let emails = vec![
+            (
+                "spam".to_string(),
+                parsed("free prize", "claim your free prize"),
+            ),
+            ("ham".to_string(), parsed("meeting", "the meeting notes")),
+        ];
+        let mut classifier = SpamClassifier::builder().min_word_count(2).build().unwrap();
+        classifier.train_parsed(&emails).unwrap();
+        let vocabulary = classifier.vocabulary();
+        assert!(vocabulary.contains(&"free"));
+        assert!(vocabulary.contains(&"meeting"));
+        assert!(!vocabulary.contains(&"claim"));
+        assert!(!vocabulary.contains(&"notes"));
+    }
+
+    #[test]
+    fn invalid_zone_weights_are_rejected() {
+        for weight in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(SpamClassifier::builder()
+                .subject_weight(weight)
+                .build()
+                .is_err());
+            assert!(SpamClassifier::builder()
+                .signature_weight(weight)
+                .build()
+                .is_err());
+        }
+        assert!(SpamClassifier::builder()
+            .signature_weight(0.0)
+            .build()
+            .is_ok());
+
+        let path = scratch_dir("negative-zone-weight").join("model.json");
+        let mut classifier = trained(SpamClassifier::new(), &SMALL);
+        classifier.subject_weight = -2.0;
+        classifier.save(&path).unwrap();
+        assert!(matches!(
+            SpamClassifier::load(&path),
+            Err(SpamError::ModelParse(_))
+        ));
+    }
+
+    #[test]
+    fn word_spaminess_leans_spam_for_spammy_words() {
+        let classifier = trained(SpamClassifier::new(), &SMALL);
+        assert!(classifier.word_spaminess("prize").unwrap() > 1.0);
+        assert!(classifier.word_spaminess("Meeting").unwrap() < -1.0);
+        assert_eq!(classifier.word_spaminess("unseen"), None);
+    }
+
+    #[test]
+    fn word_spaminess_normalizes_like_training() {
+        let classifier = trained(
+            SpamClassifier::builder()
+                .phonetic(true)
+                .collapse_repeats(true)
+                .normalize_numbers(true)
+                .build()
+                .unwrap(),
+            &[
+                ("spam", "win a free prize worth 500 now"),
+                ("spam", "claim your free lottery prize of 100"),
+                ("ham", "open the window before the meeting"),
+                ("ham", "the meeting notes are attached"),
+            ],
+        );
+        assert!(classifier.word_spaminess("prize").unwrap() > 0.0);
+        assert!(classifier.word_spaminess("FREEEE").unwrap() > 0.0);
+        assert!(classifier.word_spaminess("250").unwrap() > 0.0);
+        assert!(classifier.word_spaminess("meeting").unwrap() < 0.0);
+    }
+
+    #[test]
+    fn zone_weights_scale_subject_and_signature_evidence() {
+        let classifier = trained(
+            SpamClassifier::builder()
+                .subject_weight(2.0)
+                .signature_weight(0.0)
+                .build()
+                .unwrap(),
+            &SMALL,
+        );
+        let in_subject = parsed("lottery prize", "the meeting notes");
+        let in_signature = parsed("", "the meeting notes\n-- \nlottery prize");
+        let without = parsed("", "the meeting notes");
+
+        let signature_score = classifier.predict_score_parsed(&in_signature);
+        assert!(classifier.predict_score_parsed(&in_subject) > signature_score);
+        assert_eq!(signature_score, classifier.predict_score_parsed(&without));
+    }
+
+    #[test]
+    fn counts_hold_one_entry_per_distinct_word() {
+        // Two letters from 0-675, so every synthetic word is a plain token
+        let code = |n: usize| {
+            let letter = |i: usize| char::from(b'a' + i as u8);
+            format!("{}{}", letter(n / 26), letter(n % 26))
+        };
+        let emails: Vec<(String, String)> = (0..10_000)
+            .map(|i| {
+                let j = i / 2;
+                if i % 2 == 0 {
+                    ("spam".to_string(), format!("bargain{} cash", code(j % 300)))
+                } else {
+                    (
+                        "ham".to_string(),
+                        format!("agenda{} project", code(j % 200)),
+                    )
+                }
+            })
+            .collect();
+        let mut classifier = SpamClassifier::new();
+        classifier.train(&emails).unwrap();
+
+        let counts = &classifier.counts;
+        assert_eq!(counts.word_counts["spam"].len(), 301);
+        assert_eq!(counts.word_counts["ham"].len(), 201);
+        assert_eq!(counts.vocabulary_size(), 502);
+        assert_eq!(counts.word_total("spam"), 10_000.0);
+        assert_eq!(counts.word_count("spam", "cash"), 5_000.0);
+    }
+
+    #[test]
+    fn trains_and_classifies_from_an_in_memory_csv() {
+        let mut classifier = SpamClassifier::new();
+        classifier
+            .train_from_csv_str(
+                "label,content\n\
+                 spam,Win a free iPhone now! Click here to claim your prize\n\
+                 spam,URGENT: claim your lottery cash prize today\n\
+                 ham,Can we move the project meeting to Thursday?\n\
+                 ham,Please review the attached quarterly report\n",
+            )
+            .unwrap();
+
+        let prediction = classifier.classify("Claim your free prize");
+        assert!(prediction.is_spam);
+        assert_eq!(prediction.label, "spam");
+        assert!(!classifier.predict("Notes from the project meeting"));
+    }
+
+    // The sample dataset written on first run, loaded back from disk
+    fn default_emails() -> Vec<(String, String)> {
+        load_data_from_reader(crate::data::DEFAULT_DATASET.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn spammy_messages_score_higher_for_spam_on_the_default_dataset() {
+        let mut classifier = SpamClassifier::new();
+        classifier.train(&default_emails()).unwrap();
+
+        let spammy = "Congratulations! Claim your free prize now!!!";
+        let plain = "Can we schedule a meeting about the project report?";
+        assert!(classifier.predict_score(spammy) > 0.5);
+        assert!(classifier.predict_score(spammy) > classifier.predict_score(plain));
+        assert!(classifier.predict(spammy));
+        assert!(!classifier.predict(plain));
+    }
+
+    #[test]
+    fn words_seen_in_one_class_only_keep_the_score_finite() {
+        for alpha in [1.0, 0.01] {
+            let classifier = trained(SpamClassifier::with_alpha(alpha).unwrap(), &SMALL);
+            // "window" is only in ham, "lottery" only in spam, "zebra" in neither
+            for message in ["window", "lottery window", "zebra"] {
+                let score = classifier.predict_score(message);
+                assert!(
+                    score.is_finite() && (0.0..=1.0).contains(&score),
+                    "{}",
+                    score
+                );
+            }
+            assert!(classifier.predict_score("window") < 0.5);
+        }
+    }
+
+    #[test]
+    fn stop_words_do_not_change_the_verdict() {
+        let classifier = trained(
+            SpamClassifier::new().with_stop_words(crate::tokenizer::default_stop_words()),
+            &SMALL,
+        );
+        let message = "claim your free prize";
+        let padded = "the claim and your free prize to the";
+        assert_eq!(
+            classifier.predict_score(padded),
+            classifier.predict_score(message)
+        );
+        assert_eq!(classifier.predict(padded), classifier.predict(message));
+
+        // "the" appears only in ham, so without the list it pulls the score down
+        let unfiltered = trained(SpamClassifier::new(), &SMALL);
+        assert!(unfiltered.predict_score(padded) < unfiltered.predict_score(message));
+    }
+
+    #[test]
+    fn bigrams_learn_phrases_as_features() {
+        let rows = [
+            ("spam", "click here for a prize"),
+            ("ham", "here are the notes, click the link"),
+        ];
+        let bigrams = trained(SpamClassifier::builder().ngram(2).build().unwrap(), &rows);
+        assert!(bigrams.vocabulary().contains(&"click here"));
+        assert!(bigrams.vocabulary().contains(&"click"));
+        assert!(bigrams.word_spaminess("click here").unwrap() > 0.0);
+
+        let unigrams = trained(SpamClassifier::new(), &rows);
+        assert!(!unigrams.vocabulary().contains(&"click here"));
+        assert!(unigrams.vocabulary().contains(&"here"));
+    }
+
+    #[test]
+    fn tf_idf_discounts_words_found_in_most_emails() {
+        let rows = [
+            ("spam", "offer jackpot"),
+            ("spam", "offer cash"),
+            ("spam", "offer cash"),
+            ("ham", "offer notes"),
+            ("ham", "notes agenda"),
+        ];
+        let naive = trained(SpamClassifier::new(), &rows);
+        let tf_idf = trained(
+            SpamClassifier::builder()
+                .scoring_mode(ScoringMode::TfIdf)
+                .build()
+                .unwrap(),
+            &rows,
+        );
+        assert!(tf_idf.word_weight("offer") < tf_idf.word_weight("jackpot"));
+
+        // How much TF-IDF scales each word's evidence relative to plain counts
+        let scale =
+            |word: &str| tf_idf.word_spaminess(word).unwrap() / naive.word_spaminess(word).unwrap();
+        assert!(scale("offer") < scale("jackpot"));
+    }
+
+    #[test]
+    fn updating_after_a_batch_equals_training_on_both() {
+        let (first, rest) = THREE_CLASSES.split_at(3);
+        let mut incremental = trained(SpamClassifier::new(), first);
+        for (label, content) in rest {
+            incremental.update(label, content);
+        }
+        let combined = trained(SpamClassifier::new(), &THREE_CLASSES);
+
+        let (a, b) = (&incremental.counts, &combined.counts);
+        assert_eq!(a.word_counts, b.word_counts);
+        assert_eq!(a.word_totals, b.word_totals);
+        assert_eq!(a.email_counts, b.email_counts);
+        assert_eq!(a.doc_freq, b.doc_freq);
+        for message in [
+            "weekend shoes sale",
+            "free lottery prize",
+            "team meeting notes",
+        ] {
+            assert_eq!(
+                incremental.predict_score(message),
+                combined.predict_score(message)
+            );
+        }
+    }
+
+    #[test]
+    fn pruned_words_stop_counting() {
+        let mut classifier = trained(SpamClassifier::new(), &SMALL);
+        assert!(classifier.word_spaminess("lottery").is_some());
+        classifier.prune(2);
+
+        assert_eq!(classifier.word_spaminess("lottery"), None);
+        assert!(classifier.word_spaminess("prize").is_some());
+        assert_eq!(
+            classifier.predict_score("lottery"),
+            classifier.predict_score("")
+        );
+        for label in ["spam", "ham"] {
+            let counted: f64 = classifier.counts.word_counts[label].values().sum();
+            assert_eq!(classifier.counts.word_total(label), counted);
+        }
+    }
+
+    #[test]
+    fn limit_vocab_keeps_the_most_frequent_words() {
+        let mut classifier = trained(SpamClassifier::new(), &SMALL);
+        let before: f64 = classifier.counts.word_total("spam");
+        classifier.limit_vocab(3);
+
+        assert_eq!(classifier.vocabulary().len(), 3);
+        assert!(classifier.vocabulary().contains(&"the"));
+        assert_eq!(classifier.counts.word_total("spam"), before);
+        // Out-of-vocabulary words are scored as the unknown bucket
+        assert!(classifier.counts.has_unknown());
+        assert_ne!(
+            classifier.predict_score("lottery"),
+            classifier.predict_score("")
+        );
+    }
+
+    #[test]
+    fn url_tokens_are_counted_per_class() {
+        let classifier = trained(
+            SpamClassifier::builder()
+                .url_features(UrlFeatures::Token)
+                .build()
+                .unwrap(),
+            &[
+                ("spam", "win at http://a.xyz and www.b.top or c.biz"),
+                ("spam", "claim your prize at https://d.io/claim"),
+                ("ham", "the agenda is on http://intranet.example.org"),
+                ("ham", "see you at lunch"),
+            ],
+        );
+        let url = crate::tokenizer::URL_TOKEN;
+        assert_eq!(classifier.counts.word_count("spam", url), 4.0);
+        assert_eq!(classifier.counts.word_count("ham", url), 1.0);
+    }
+
+    #[test]
+    fn shouting_raises_the_score() {
+        let classifier = trained(
+            SpamClassifier::builder()
+                .structural_features(true)
+                .build()
+                .unwrap(),
+            &[
+                ("spam", "WIN A FREE PRIZE NOW!!!"),
+                ("spam", "CLAIM YOUR CASH TODAY!!"),
+                ("ham", "call me about the report today."),
+                ("ham", "the offer letter is attached."),
+            ],
+        );
+
+        let shouted = "CALL US ABOUT THE OFFER TODAY!!!";
+        let calm = "call us about the offer today.";
+        assert!(classifier.predict_score(shouted) > classifier.predict_score(calm));
+        let explained: Vec<String> = classifier
+            .explain(shouted)
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect();
+        assert!(explained.contains(&crate::tokenizer::CAPS_HIGH_TOKEN.to_string()));
+        assert!(explained.contains(&crate::tokenizer::EXCLAMATION_TOKEN.to_string()));
+    }
+
+    #[test]
+    fn training_needs_emails_of_two_classes() {
+        let mut classifier = SpamClassifier::new();
+        assert!(matches!(
+            classifier.train(&[]),
+            Err(SpamError::EmptyDataset)
+        ));
+        assert!(matches!(
+            classifier.train(&dataset(&[("ham", "see you"), ("ham", "notes")])),
+            Err(SpamError::MissingClass(class)) if class == "spam"
+        ));
+        assert!(matches!(
+            classifier.train(&dataset(&[("spam", "win now")])),
+            Err(SpamError::MissingClass(class)) if class == "ham"
+        ));
+        assert!(!classifier.is_trained());
+
+        // A later batch of one class is fine once the model knows both
+        classifier.train(&dataset(&SMALL)).unwrap();
+        classifier.train(&dataset(&[("spam", "win now")])).unwrap();
+    }
+
+    #[test]
+    fn tuned_threshold_separates_separable_classes() {
+        let classifier = trained(SpamClassifier::new(), &SMALL);
+        let validation = dataset(&[
+            ("spam", "free prize"),
+            ("spam", "claim your lottery prize now"),
+            ("spam", "win free"),
+            ("ham", "meeting notes attached"),
+            ("ham", "open the window"),
+            ("ham", "before the meeting"),
+        ]);
+        let threshold = classifier.tune_threshold(&validation);
+
+        for (label, content) in &validation {
+            let score = classifier.predict_score(content);
+            assert_eq!(score > threshold, label == "spam", "{} {}", content, score);
+        }
+    }
+
+    #[test]
+    fn feature_vectors_leave_out_unknown_words() {
+        let classifier = trained(SpamClassifier::new(), &SMALL);
+        let features = classifier.feature_vector("Free FREE prize for the zebra");
+        let expected: HashMap<String, usize> = [("free", 2), ("prize", 1), ("the", 1)]
+            .into_iter()
+            .map(|(word, count)| (word.to_string(), count))
+            .collect();
+        assert_eq!(features, expected);
+
+        let vocabulary = classifier.vocabulary();
+        assert!(vocabulary.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(features
+            .keys()
+            .all(|word| vocabulary.contains(&word.as_str())));
+        assert!(!vocabulary.contains(&"zebra"));
+    }
+
+    #[test]
+    fn case_sensitive_models_learn_caps_separately() {
+        let rows = [
+            ("spam", "FREE prize inside"),
+            ("ham", "free parking at the office"),
+        ];
+        let sensitive = trained(
+            SpamClassifier::builder()
+                .case_sensitive(true)
+                .build()
+                .unwrap(),
+            &rows,
+        );
+        assert!(sensitive.vocabulary().contains(&"FREE"));
+        assert!(sensitive.vocabulary().contains(&"free"));
+        assert!(sensitive.word_spaminess("FREE").unwrap() > 0.0);
+        assert!(sensitive.word_spaminess("free").unwrap() < 0.0);
+
+        let folded = trained(SpamClassifier::new(), &rows);
+        assert!(!folded.vocabulary().contains(&"FREE"));
+        assert_eq!(folded.word_spaminess("FREE"), folded.word_spaminess("free"));
+    }
+
+    #[test]
+    fn emoji_add_to_the_spam_score() {
+        let classifier = trained(
+            SpamClassifier::new(),
+            &[
+                ("spam", "🎁 claim your gift 💰"),
+                ("spam", "🚨 account locked 💰 verify now"),
+                ("ham", "lunch at noon?"),
+                ("ham", "the gift for mum is wrapped"),
+            ],
+        );
+        assert!(classifier.word_spaminess("💰").unwrap() > 0.0);
+        assert!(classifier.predict_score("your gift 💰") > classifier.predict_score("your gift"));
+    }
+
+    #[test]
+    fn feature_selection_drops_balanced_filler_words() {
+        let mut classifier = trained(
+            SpamClassifier::new(),
+            &[
+                ("spam", "regards jackpot"),
+                ("spam", "regards jackpot"),
+                ("ham", "regards agenda"),
+                ("ham", "regards agenda"),
+            ],
+        );
+        classifier.select_features(2);
+        assert_eq!(classifier.vocabulary(), ["agenda", "jackpot"]);
+    }
+
+    #[test]
+    fn char_ngrams_see_through_obfuscated_words() {
+        let rows = [
+            ("spam", "free money waiting"),
+            ("spam", "get free money fast"),
+            ("ham", "the quarterly report is attached"),
+            ("ham", "notes from the team meeting"),
+        ];
+        let grams = trained(
+            SpamClassifier::builder().char_ngram(3).build().unwrap(),
+            &rows,
+        );
+        let obfuscated = "fr33 m0ney";
+        let features = grams.feature_vector(obfuscated);
+        assert!(features.contains_key("#<fr"));
+        assert!(features.contains_key("#ey>"));
+        assert!(grams.predict(obfuscated));
+
+        // Word features alone know nothing about the obfuscated words
+        let words = trained(SpamClassifier::new(), &rows);
+        assert!(words.feature_vector(obfuscated).is_empty());
+    }
+
+    #[test]
+    fn scores_near_the_threshold_are_uncertain() {
+        let classifier = trained(
+            SpamClassifier::builder()
+                .uncertainty_band(0.2)
+                .build()
+                .unwrap(),
+            &SMALL,
+        );
+        // One spam word and one ham word, each seen once
+        let balanced = "lottery window";
+        let score = classifier.predict_score(balanced);
+        assert!((score - 0.5).abs() < 0.1, "{}", score);
+        assert_eq!(classifier.predict_label(balanced), UNCERTAIN_LABEL);
+        assert_eq!(classifier.predict_label("free prize"), "spam");
+        assert_eq!(classifier.predict_label("meeting notes"), "ham");
+    }
+
+    #[test]
+    fn collapsed_repeats_share_a_feature() {
+        let classifier = trained(
+            SpamClassifier::builder()
+                .collapse_repeats(true)
+                .build()
+                .unwrap(),
+            &SMALL,
+        );
+        assert_eq!(
+            classifier.feature_vector("FREEEEE prize"),
+            classifier.feature_vector("free prize")
+        );
+    }
+
+    #[test]
+    fn streaming_training_matches_batch_training() {
+        let path = scratch_dir("streaming").join("emails.csv");
+        crate::data::create_default_dataset(&path).unwrap();
+        let mut streamed = SpamClassifier::new();
+        assert_eq!(streamed.train_streaming(&path).unwrap(), 20);
+        let mut batch = SpamClassifier::new();
+        batch
+            .train(&crate::data::load_data(&path).unwrap())
+            .unwrap();
+
+        let (a, b) = (&streamed.counts, &batch.counts);
+        assert_eq!(a.word_counts, b.word_counts);
+        assert_eq!(a.word_totals, b.word_totals);
+        assert_eq!(a.email_counts, b.email_counts);
+        assert_eq!(a.doc_freq, b.doc_freq);
+        assert_eq!(a.languages, b.languages);
+    }
+
+    #[test]
+    fn whitelist_and_blacklist_override_the_model() {
+        let classifier = trained(
+            SpamClassifier::new()
+                .with_whitelist(vec!["Acme Corp".to_string()])
+                .with_blacklist(vec!["wire transfer".to_string()]),
+            &SMALL,
+        );
+        let spammy = "free prize from ACME CORP";
+        let hammy = "meeting notes: confirm the wire transfer";
+        assert!(!classifier.predict(spammy));
+        assert_eq!(classifier.predict_score(spammy), 0.0);
+        assert!(classifier.predict(hammy));
+        assert_eq!(classifier.predict_score(hammy), 1.0);
+        // The whitelist wins when both match
+        assert!(!classifier.predict("acme corp wire transfer"));
+
+        let plain = trained(SpamClassifier::new(), &SMALL);
+        assert!(plain.predict(spammy));
+        assert!(!plain.predict(hammy));
+    }
+
+    #[test]
+    fn misclassified_returns_the_known_errors() {
+        let classifier = trained(SpamClassifier::new(), &SMALL);
+        let test = dataset(&[
+            ("spam", "free prize"),
+            ("spam", "meeting notes attached"),
+            ("ham", "the meeting"),
+            ("ham", "claim your lottery prize"),
+        ]);
+        let wrong: Vec<(&str, &str)> = classifier
+            .misclassified(&test)
+            .map(|(content, label, _)| (content, label))
+            .collect();
+        assert_eq!(
+            wrong,
+            [
+                ("meeting notes attached", "spam"),
+                ("claim your lottery prize", "ham")
+            ]
+        );
+        let (_, _, score) = classifier.misclassified(&test).next().unwrap();
+        assert_eq!(score, classifier.predict_score("meeting notes attached"));
+    }
+
+    #[test]
+    fn foreign_messages_are_flagged_as_a_language_mismatch() {
+        let mut classifier = SpamClassifier::new();
+        classifier.train(&default_emails()).unwrap();
+        assert_eq!(classifier.dominant_language(), Some("eng"));
+
+        let french = "Bonjour, pouvez-vous m'envoyer le rapport trimestriel avant la réunion de demain matin ?";
+        assert_eq!(classifier.language_mismatch(french), Some("fra"));
+        let english = "Could you send me the quarterly report before tomorrow morning's meeting?";
+        assert_eq!(classifier.language_mismatch(english), None);
+    }
+
+    #[test]
+    fn only_a_sample_of_training_emails_has_its_language_detected() {
+        let english = "Please find attached the quarterly report for your review. Let me know \
+                       if you have any questions before the meeting tomorrow morning.";
+        let emails: Vec<(String, String)> = (0..LANGUAGE_SAMPLE + 200)
+            .map(|i| {
+                let label = if i % 2 == 0 { "spam" } else { "ham" };
+                (label.to_string(), english.to_string())
+            })
+            .collect();
+        let mut classifier = SpamClassifier::new();
+        classifier.train(&emails).unwrap();
+        assert_eq!(classifier.counts.languages["eng"], LANGUAGE_SAMPLE as f64);
+
+        // Languages fade with decay like every other count
+        let mut decaying = SpamClassifier::new().with_decay(0.5);
+        decaying.train(&emails[..10]).unwrap();
+        let before = decaying.counts.languages["eng"];
+        let french = "Bonjour, pouvez-vous m'envoyer le rapport trimestriel avant la réunion de demain matin ?";
+        for _ in 0..10 {
+            decaying.update("ham", french);
+        }
+        assert!(decaying.counts.languages["eng"] < before / 100.0);
+        assert_eq!(decaying.dominant_language(), Some("fra"));
+    }
+
+    #[test]
+    fn a_spam_only_sender_domain_tips_a_borderline_message() {
+        let sent = |from: &str, subject: &str, body: &str| ParsedEmail {
+            subject: subject.to_string(),
+            from: from.to_string(),
+            body: body.to_string(),
+        };
+        let mut classifier = SpamClassifier::new();
+        classifier
+            .train_parsed(&[
+                (
+                    "spam".to_string(),
+                    sent("x@deals.top", "free prize", "claim now"),
+                ),
+                (
+                    "spam".to_string(),
+                    sent("y@deals.top", "lottery", "you won"),
+                ),
+                (
+                    "ham".to_string(),
+                    sent("boss@work.com", "meeting", "notes attached"),
+                ),
+                (
+                    "ham".to_string(),
+                    sent("pat@work.com", "lunch", "claim a table"),
+                ),
+            ])
+            .unwrap();
+
+        let unknown = sent("someone@elsewhere.org", "hello", "claim it");
+        let from_spammer = sent("z@DEALS.top", "hello", "claim it");
+        let borderline = classifier.predict_score_parsed(&unknown);
+        assert!((borderline - 0.5).abs() < 0.1, "{}", borderline);
+        assert!(!classifier.predict_parsed(&unknown));
+        assert!(classifier.predict_parsed(&from_spammer));
+
+        // Without a sender the reputation adds nothing
+        let anonymous = sent("", "hello", "claim it");
+        assert_eq!(classifier.predict_score_parsed(&anonymous), borderline);
+    }
+
+    #[test]
+    fn prediction_fields_agree_with_each_other() {
+        let classifier = trained(SpamClassifier::new(), &SMALL);
+        for message in [
+            "win a free lottery prize now before the window closes",
+            "the meeting notes",
+            "zebra",
+        ] {
+            let prediction = classifier.classify(message);
+            assert_eq!(prediction.is_spam, classifier.predict(message));
+            assert_eq!(prediction.score, classifier.predict_score(message));
+            assert_eq!(
+                prediction.is_spam,
+                prediction.score > classifier.threshold()
+            );
+            assert_eq!(
+                prediction.label,
+                if prediction.is_spam { "spam" } else { "ham" }
+            );
+            assert!(prediction.top_features.len() <= TOP_FEATURES);
+            let explained = classifier.explain(message);
+            assert_eq!(
+                prediction.top_features[..],
+                explained[..prediction.top_features.len()]
+            );
+        }
+        assert_eq!(
+            classifier
+                .classify("win a free lottery prize now before the window closes")
+                .top_features
+                .len(),
+            TOP_FEATURES
+        );
+    }
+
+    #[test]
+    fn training_from_a_string_matches_training_from_a_file() {
+        let path = scratch_dir("from-string").join("emails.csv");
+        crate::data::create_default_dataset(&path).unwrap();
+        let mut from_string = SpamClassifier::new();
+        from_string
+            .train_from_csv_str(&fs::read_to_string(&path).unwrap())
+            .unwrap();
+        let mut from_file = SpamClassifier::new();
+        from_file
+            .train(&crate::data::load_data(&path).unwrap())
+            .unwrap();
+
+        let (a, b) = (&from_string.counts, &from_file.counts);
+        assert_eq!(a.word_counts, b.word_counts);
+        assert_eq!(a.word_totals, b.word_totals);
+        assert_eq!(a.email_counts, b.email_counts);
+        assert_eq!(from_string.training_emails(), 20);
+    }
+
+    #[test]
+    fn calibrated_scores_track_observed_spam_frequency() {
+        // Long messages make Naive Bayes overconfident, and one email in five
+        // carries the other class's label, so a raw score near 1.0 overstates
+        // how often such emails are really spam
+        let noisy = |offset: usize| -> Vec<(String, String)> {
+            (offset..offset + 200)
+                .map(|i| {
+                    let spammy = i % 2 == 0;
+                    let content = if spammy {
+                        format!("win a free prize claim your cash offer now today {}", i % 7)
+                    } else {
+                        format!(
+                            "the meeting notes and agenda for the project review {}",
+                            i % 7
+                        )
+                    };
+                    let flipped = i % 5 == 0;
+                    let label = if spammy != flipped { "spam" } else { "ham" };
+                    (label.to_string(), content)
+                })
+                .collect()
+        };
+        let (training, held_out, validation) = (noisy(0), noisy(500), noisy(1000));
+        let brier = |classifier: &SpamClassifier| -> f64 {
+            validation
+                .iter()
+                .map(|(label, content)| {
+                    let observed = if label == "spam" { 1.0 } else { 0.0 };
+                    (classifier.predict_score(content) - observed).powi(2)
+                })
+                .sum::<f64>()
+                / validation.len() as f64
+        };
+
+        let mut classifier = SpamClassifier::new();
+        classifier.train(&training).unwrap();
+        let raw = brier(&classifier);
+        classifier.calibrate(&held_out).unwrap();
+        let calibrated = brier(&classifier);
+        assert!(calibrated < raw, "calibrated {} vs raw {}", calibrated, raw);
+
+        // About 80% of the emails that look like spam are labeled spam
+        let score = classifier.predict_score("win a free prize claim your cash offer now today 3");
+        assert!((score - 0.8).abs() < 0.1, "{}", score);
+    }
+
+    #[test]
+    fn summary_priors_sum_to_one_and_vocabulary_counts_distinct_tokens() {
+        for rows in [&SMALL[..], &THREE_CLASSES[..]] {
+            let classifier = trained(SpamClassifier::new(), rows);
+            let summary = classifier.summary();
+
+            let total: f64 = summary.priors.iter().map(|(_, prior)| prior).sum();
+            assert!((total - 1.0).abs() < 1e-9, "{:?}", summary.priors);
+
+            let distinct: HashSet<String> = rows
+                .iter()
+                .flat_map(|(_, content)| classifier.tokens(content))
+                .collect();
+            assert_eq!(summary.vocabulary_size, distinct.len());
+        }
+
+        let summary = trained(SpamClassifier::new(), &SMALL).summary();
+        assert_eq!((summary.spam_count, summary.ham_count), (2, 2));
+        assert!(summary.to_string().contains("P(spam) = 0.500"));
+    }
+
+    #[test]
+    fn a_heavier_spam_weight_raises_recall() {
+        // Spam that borrows the vocabulary of ham is missed at equal weights
+        let test = dataset(&[
+            ("spam", "free prize"),
+            ("spam", "the meeting prize"),
+            ("spam", "meeting notes about your lottery"),
+            ("ham", "the meeting notes"),
+        ]);
+        let recall = |weights: (f64, f64)| {
+            trained(SpamClassifier::new().with_class_weights(weights), &SMALL)
+                .evaluate(&test)
+                .recall
+        };
+        assert!(recall((1.0, 1.0)) < 1.0);
+        assert!(recall((20.0, 1.0)) > recall((1.0, 1.0)));
+    }
+
+    #[test]
+    fn decayed_updates_fade_words_seen_only_in_old_data() {
+        let updated = |decay: f64| {
+            let mut classifier = trained(SpamClassifier::new().with_decay(decay), &SMALL);
+            for _ in 0..50 {
+                classifier.update("spam", "cheap crypto wallet bonus");
+                classifier.update("ham", "sprint retro standup agenda");
+            }
+            classifier
+        };
+        let kept = updated(1.0);
+        let decayed = updated(0.9);
+
+        let old = |classifier: &SpamClassifier| classifier.word_spaminess("prize").unwrap();
+        assert!(old(&kept) > 1.0, "{}", old(&kept));
+        assert!(old(&decayed) < 0.1 * old(&kept), "{}", old(&decayed));
+        assert!((decayed.predict_score("free prize") - 0.5).abs() < 0.1);
+        assert!(kept.predict("free prize"));
+
+        // The new vocabulary is learned just as well either way
+        assert!(decayed.predict("crypto bonus"));
+        assert!(!decayed.predict("standup agenda"));
+    }
+
+    #[test]
+    fn an_untrained_model_cannot_classify() {
+        let mut classifier = SpamClassifier::new();
+        assert!(!classifier.is_trained());
+        assert!(matches!(
+            classifier.try_classify("win a free prize"),
+            Err(SpamError::Untrained)
+        ));
+
+        classifier.train(&dataset(&SMALL)).unwrap();
+        assert!(classifier.try_classify("win a free prize").unwrap().is_spam);
+
+        classifier.clear();
+        assert!(matches!(
+            classifier.try_classify("win a free prize"),
+            Err(SpamError::Untrained)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "untrained model")]
+    fn predict_on_an_untrained_model_panics() {
+        SpamClassifier::new().predict("win a free prize");
+    }
+
+    #[test]
+    fn merging_two_models_equals_training_on_both_datasets() {
+        let (first, second) = THREE_CLASSES.split_at(3);
+        let mut merged = trained(SpamClassifier::new(), first);
+        merged
+            .merge(&trained(SpamClassifier::new(), second))
+            .unwrap();
+        let combined = trained(SpamClassifier::new(), &THREE_CLASSES);
+
+        let (a, b) = (&merged.counts, &combined.counts);
+        assert_eq!(a.word_counts, b.word_counts);
+        assert_eq!(a.word_totals, b.word_totals);
+        assert_eq!(a.email_counts, b.email_counts);
+        assert_eq!(a.doc_freq, b.doc_freq);
+        assert_eq!(
+            merged.predict_score("free lottery prize"),
+            combined.predict_score("free lottery prize")
+        );
+
+        let bigrams = trained(SpamClassifier::new().with_ngram(2), second);
+        match merged.merge(&bigrams) {
+            Err(SpamError::InvalidSetting(message)) => assert!(message.contains("ngram")),
+            other => panic!("expected a settings mismatch, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn restoring_a_snapshot_undoes_an_update() {
+        let mut classifier = trained(SpamClassifier::new(), &SMALL);
+        let before = classifier.predict_score("free meeting");
+        let snapshot = classifier.snapshot();
+
+        classifier.update("spam", "free meeting reminder");
+        assert_ne!(classifier.predict_score("free meeting"), before);
+
+        classifier.restore(snapshot);
+        let fresh = trained(SpamClassifier::new(), &SMALL);
+        let (a, b) = (&classifier.counts, &fresh.counts);
+        assert_eq!(a.word_counts, b.word_counts);
+        assert_eq!(a.word_totals, b.word_totals);
+        assert_eq!(a.email_counts, b.email_counts);
+        assert_eq!(a.doc_freq, b.doc_freq);
+        assert_eq!(classifier.predict_score("free meeting"), before);
+    }
+
+    #[test]
+    fn phonetic_spellings_share_a_spam_feature() {
+        let rows = [
+            ("spam", "cheap medicine online"),
+            ("spam", "buy medecine today"),
+            ("ham", "the meeting notes are attached"),
+            ("ham", "lunch with the team today"),
+        ];
+        let phonetic = trained(SpamClassifier::new().with_phonetic(true), &rows);
+        assert_eq!(phonetic.tokens("medicine"), phonetic.tokens("medecine"));
+        assert_eq!(phonetic.tokens("medicine").len(), 1);
+        // Both spellings were counted under the one feature
+        let feature = &phonetic.tokens("medicine")[0];
+        assert_eq!(phonetic.counts.word_count("spam", feature), 2.0);
+        assert!(phonetic.predict("medisin"));
+
+        let plain = trained(SpamClassifier::new(), &rows);
+        assert_ne!(plain.tokens("medicine"), plain.tokens("medecine"));
+        assert!(phonetic.predict_score("medicine") > plain.predict_score("medicine"));
+    }
+
+    #[test]
+    fn a_cleared_model_behaves_like_a_fresh_one() {
+        let configured = || {
+            SpamClassifier::builder()
+                .alpha(0.5)
+                .threshold(0.7)
+                .ngram(2)
+                .build()
+                .unwrap()
+        };
+        let mut classifier = trained(configured(), &SMALL);
+        classifier.update("spam", "free meeting reminder");
+        classifier.clear();
+
+        assert!(!classifier.is_trained());
+        assert!(matches!(
+            classifier.try_classify("free prize"),
+            Err(SpamError::Untrained)
+        ));
+        assert_eq!(classifier.alpha, 0.5);
+        assert_eq!(classifier.threshold(), 0.7);
+        assert_eq!(classifier.vocab_size(), 0);
+
+        classifier.train(&dataset(&THREE_CLASSES)).unwrap();
+        let fresh = trained(configured(), &THREE_CLASSES);
+        for message in ["free prize", "weekend shoes sale", "team meeting notes"] {
+            assert_eq!(
+                classifier.predict_score(message),
+                fresh.predict_score(message)
+            );
+        }
+    }
+
+    #[test]
+    fn bernoulli_counts_a_repeated_word_once_per_email() {
+        let rows = [
+            ("spam", "free free free free prize"),
+            ("ham", "the meeting notes are attached"),
+        ];
+        let multinomial = trained(SpamClassifier::new(), &rows);
+        let bernoulli = trained(
+            SpamClassifier::new().with_model_variant(ModelVariant::Bernoulli),
+            &rows,
+        );
+        assert_eq!(multinomial.counts.word_count("spam", "free"), 4.0);
+        assert_eq!(bernoulli.counts.word_count("spam", "free"), 1.0);
+        assert_eq!(bernoulli.counts.word_count("spam", "prize"), 1.0);
+
+        // Scoring follows the same rule: repeating a word adds no evidence
+        let quoted = "free free free free free free";
+        assert!(multinomial.predict_score(quoted) > multinomial.predict_score("free"));
+        assert_eq!(
+            bernoulli.predict_score(quoted),
+            bernoulli.predict_score("free")
+        );
+    }
+
+    #[test]
+    fn a_minimum_length_drops_single_letter_tokens() {
+        let rows = [
+            ("spam", "win $ x prize q now"),
+            ("ham", "see u at the meeting b"),
+        ];
+        let known = |classifier: &SpamClassifier, word: &str| classifier.counts.knows(word);
+
+        let plain = trained(SpamClassifier::new(), &rows);
+        assert!(known(&plain, "x") && known(&plain, "u") && known(&plain, "$"));
+
+        let filtered = trained(
+            SpamClassifier::new()
+                .with_min_token_len(2)
+                .with_keep_short_tokens(vec!["$".to_string()]),
+            &rows,
+        );
+        for word in ["x", "q", "u", "b"] {
+            assert!(!known(&filtered, word), "{}", word);
+        }
+        assert!(known(&filtered, "$"));
+        assert!(known(&filtered, "prize") && known(&filtered, "meeting"));
+        assert!(filtered.tokens("x prize").iter().all(|token| token != "x"));
+    }
+
+    #[test]
+    fn trains_from_arrays_and_sub_slices() {
+        let array = [
+            ("spam".to_string(), "win a free prize now".to_string()),
+            (
+                "ham".to_string(),
+                "the meeting notes are attached".to_string(),
+            ),
+        ];
+        let mut from_array = SpamClassifier::new();
+        from_array.train(&array).unwrap();
+        assert!(from_array.predict("free prize"));
+
+        // A fold borrowed from a larger dataset trains like an owned copy of it
+        let emails = dataset(&THREE_CLASSES);
+        let mut from_slice = SpamClassifier::new();
+        from_slice.train(&emails[1..5]).unwrap();
+        let copied = trained(SpamClassifier::new(), &THREE_CLASSES[1..5]);
+        assert_eq!(from_slice.counts.total_emails(), 4.0);
+        assert_eq!(from_slice.counts.word_counts, copied.counts.word_counts);
+    }
+
+    #[test]
+    fn batch_training_counts_like_updates_without_decay() {
+        let decaying = trained(SpamClassifier::new().with_decay(0.5), &THREE_CLASSES);
+        let mut updated = SpamClassifier::new();
+        for (label, content) in THREE_CLASSES {
+            updated.update(label, content);
+        }
+
+        let (a, b) = (&decaying.counts, &updated.counts);
+        assert_eq!(a.word_counts, b.word_counts);
+        assert_eq!(a.word_totals, b.word_totals);
+        assert_eq!(a.email_counts, b.email_counts);
+        assert_eq!(a.doc_freq, b.doc_freq);
+        assert_eq!(a.languages, b.languages);
+    }
+}
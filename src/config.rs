@@ -0,0 +1,245 @@
+use crate::classifier::{ModelVariant, ScoringMode, SpamClassifier, SpamClassifierBuilder};
+use crate::error::SpamError;
+use crate::tokenizer::{load_stop_words, UrlFeatures};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Where the command-line tool looks for its config when none is given
+pub const CONFIG_FILE_PATH: &str = "spamdetect.toml";
+
+// Defaults for every classifier setting and file path, read from a TOML file such as
+//
+//     model = "model.json"
+//     data = "emails.csv"
+//     alpha = 0.5
+//     ngram = 2
+//     scoring_mode = "TfIdf"
+//
+// Settings left out keep the classifier's own defaults. Enum values use the
+// variant names, e.g. url_features = "TokenAndDomain".
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub model: Option<PathBuf>,
+    pub data: Option<PathBuf>,
+    // Text file with one stop word per line
+    pub stop_words: Option<PathBuf>,
+    pub alpha: Option<f64>,
+    pub min_token_len: Option<usize>,
+    pub keep_short_tokens: Option<Vec<String>>,
+    pub threshold: Option<f64>,
+    pub ngram: Option<usize>,
+    pub scoring_mode: Option<ScoringMode>,
+    pub model_variant: Option<ModelVariant>,
+    pub min_word_count: Option<usize>,
+    pub strip_html: Option<bool>,
+    pub url_features: Option<UrlFeatures>,
+    pub structural_features: Option<bool>,
+    pub subject_weight: Option<f64>,
+    pub signature_weight: Option<f64>,
+    pub case_sensitive: Option<bool>,
+    pub char_ngram: Option<usize>,
+    pub uncertainty_band: Option<f64>,
+    pub collapse_repeats: Option<bool>,
+    pub normalize_numbers: Option<bool>,
+    pub phonetic: Option<bool>,
+    pub whitelist: Option<Vec<String>>,
+    pub blacklist: Option<Vec<String>>,
+    pub spam_keywords: Option<Vec<String>>,
+    pub keyword_boost: Option<f64>,
+    // (spam, ham) prior weights, written as e.g. class_weights = [2.0, 1.0]
+    pub class_weights: Option<(f64, f64)>,
+    pub decay: Option<f64>,
+}
+
+impl Config {
+    // Read and validate a config file. Both syntax errors and out-of-range values
+    // are reported as SpamError::Config naming the file.
+    pub fn load(path: &Path) -> Result<Config, SpamError> {
+        let text = fs::read_to_string(path)?;
+        Config::parse(&text).map_err(|e| match e {
+            SpamError::Config(msg) | SpamError::InvalidSetting(msg) => {
+                SpamError::Config(format!("{}: {}", path.display(), msg))
+            }
+            e => e,
+        })
+    }
+
+    // Parse and validate config text
+    pub fn parse(text: &str) -> Result<Config, SpamError> {
+        let config: Config = toml::from_str(text).map_err(|e| SpamError::Config(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Check every setting against the ranges the classifier accepts
+    pub fn validate(&self) -> Result<(), SpamError> {
+        if self.ngram == Some(0) {
+            return Err(SpamError::InvalidSetting(
+                "ngram must be at least 1, got 0".to_string(),
+            ));
+        }
+        self.apply(SpamClassifier::builder()).build().map(|_| ())
+    }
+
+    // A builder with every configured setting applied, including the stop words
+    // read from the configured file
+    pub fn builder(&self) -> Result<SpamClassifierBuilder, SpamError> {
+        let mut builder = self.apply(SpamClassifier::builder());
+        if let Some(path) = &self.stop_words {
+            builder = builder.stop_words(load_stop_words(path)?);
+        }
+        Ok(builder)
+    }
+
+    // An untrained classifier with every configured setting
+    pub fn classifier(&self) -> Result<SpamClassifier, SpamError> {
+        self.builder()?.build()
+    }
+
+    fn apply(&self, mut builder: SpamClassifierBuilder) -> SpamClassifierBuilder {
+        if let Some(alpha) = self.alpha {
+            builder = builder.alpha(alpha);
+        }
+        if let Some(threshold) = self.threshold {
+            builder = builder.threshold(threshold);
+        }
+        if let Some(min_token_len) = self.min_token_len {
+            builder = builder.min_token_len(min_token_len);
+        }
+        if let Some(keep_short_tokens) = &self.keep_short_tokens {
+            builder = builder.keep_short_tokens(keep_short_tokens.clone());
+        }
+        if let Some(ngram) = self.ngram {
+            builder = builder.ngram(ngram);
+        }
+        if let Some(scoring_mode) = self.scoring_mode {
+            builder = builder.scoring_mode(scoring_mode);
+        }
+        if let Some(model_variant) = self.model_variant {
+            builder = builder.model_variant(model_variant);
+        }
+        if let Some(min_word_count) = self.min_word_count {
+            builder = builder.min_word_count(min_word_count);
+        }
+        if let Some(strip_html) = self.strip_html {
+            builder = builder.strip_html(strip_html);
+        }
+        if let Some(url_features) = self.url_features {
+            builder = builder.url_features(url_features);
+        }
+        if let Some(structural_features) = self.structural_features {
+            builder = builder.structural_features(structural_features);
+        }
+        if let Some(subject_weight) = self.subject_weight {
+            builder = builder.subject_weight(subject_weight);
+        }
+        if let Some(signature_weight) = self.signature_weight {
+            builder = builder.signature_weight(signature_weight);
+        }
+        if let Some(case_sensitive) = self.case_sensitive {
+            builder = builder.case_sensitive(case_sensitive);
+        }
+        if let Some(char_ngram) = self.char_ngram {
+            builder = builder.char_ngram(char_ngram);
+        }
+        if let Some(uncertainty_band) = self.uncertainty_band {
+            builder = builder.uncertainty_band(uncertainty_band);
+        }
+        if let Some(collapse_repeats) = self.collapse_repeats {
+            builder = builder.collapse_repeats(collapse_repeats);
+        }
+        if let Some(normalize_numbers) = self.normalize_numbers {
+            builder = builder.normalize_numbers(normalize_numbers);
+        }
+        if let Some(phonetic) = self.phonetic {
+            builder = builder.phonetic(phonetic);
+        }
+        if let Some(whitelist) = &self.whitelist {
+            builder = builder.whitelist(whitelist.clone());
+        }
+        if let Some(blacklist) = &self.blacklist {
+            builder = builder.blacklist(blacklist.clone());
+        }
+        if let Some(spam_keywords) = &self.spam_keywords {
+            builder = builder.spam_keywords(spam_keywords.clone());
+        }
+        if let Some(keyword_boost) = self.keyword_boost {
+            builder = builder.keyword_boost(keyword_boost);
+        }
+        if let Some(class_weights) = self.class_weights {
+            builder = builder.class_weights(class_weights);
+        }
+        if let Some(decay) = self.decay {
+            builder = builder.decay(decay);
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    #[test]
+    fn zero_alpha_is_rejected() {
+        assert!(matches!(
+            Config::parse("alpha = 0.0"),
+            Err(SpamError::InvalidSetting(_))
+        ));
+        assert!(Config::parse("alpha = 0.5").is_ok());
+    }
+
+    #[test]
+    fn negative_zone_weights_are_rejected() {
+        assert!(Config::parse("subject_weight = -1.0").is_err());
+        assert!(Config::parse("signature_weight = -0.5").is_err());
+        assert!(Config::parse("subject_weight = 2.0\nsignature_weight = 0.0").is_ok());
+    }
+
+    #[test]
+    fn loaded_settings_reach_the_classifier() {
+        let dir = scratch_dir("config-settings");
+        let path = dir.join("spamdetect.toml");
+        fs::write(
+            &path,
+            "model = \"custom.json\"\n\
+             threshold = 0.7\n\
+             ngram = 2\n\
+             scoring_mode = \"TfIdf\"\n\
+             spam_keywords = [\"Jackpot\"]\n\
+             class_weights = [2.0, 1.0]\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.model, Some(PathBuf::from("custom.json")));
+        assert_eq!(config.scoring_mode, Some(ScoringMode::TfIdf));
+        assert_eq!(config.data, None);
+
+        let mut classifier = config.classifier().unwrap();
+        assert_eq!(classifier.threshold(), 0.7);
+        assert_eq!(classifier.class_weights(), (2.0, 1.0));
+        assert_eq!(classifier.spam_keywords(), ["jackpot"]);
+        classifier
+            .train_from_csv_str("label,content\nspam,click here now\nham,see you soon\n")
+            .unwrap();
+        assert!(classifier.vocabulary().contains(&"click here"));
+    }
+
+    #[test]
+    fn invalid_config_values_name_the_file() {
+        let dir = scratch_dir("config-invalid");
+        let path = dir.join("bad-threshold.toml");
+        fs::write(&path, "threshold = 1.5\n").unwrap();
+
+        let error = Config::load(&path).unwrap_err();
+        assert!(matches!(error, SpamError::Config(_)));
+        let message = error.to_string();
+        assert!(message.contains("bad-threshold.toml"), "{}", message);
+        assert!(message.contains("1.5"), "{}", message);
+        assert!(Config::parse("treshold = 0.5").is_err());
+    }
+}
@@ -0,0 +1,703 @@
+use crate::error::SpamError;
+use csv::ReaderBuilder;
+use flate2::read::MultiGzDecoder;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// First two bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Labeled emails as (label, content) pairs
+pub type Dataset = Vec<(String, String)>;
+
+// The CSV written by create_default_dataset. Content containing commas is quoted.
+pub(crate) const DEFAULT_DATASET: &str = "label,content\n\
+    spam,Congratulations! You've won a free iPhone! Click here to claim now!!!\n\
+    ham,\"Hi John, can we schedule a meeting to discuss the project next week?\"\n\
+    spam,\"URGENT: You've been selected for an exclusive lottery. Claim your $10,000 prize NOW!\"\n\
+    ham,Please find attached the quarterly report for your review.\n\
+    spam,GET RICH QUICK! Make $5000 per week working from home. No experience needed!\n\
+    ham,Meeting minutes from today's team discussion are attached.\n\
+    spam,Limited time offer! 90% OFF all products. Buy now before it's gone!\n\
+    ham,Could you please send me the updated client contact list?\n\
+    spam,You are the WINNER of our mega sweepstakes! Claim your prize immediately!\n\
+    ham,Thank you for your recent order. Your package will be shipped soon.\n\
+    spam,FREE VIAGRA! Lowest prices guaranteed. Buy now!\n\
+    ham,Please confirm your attendance for the upcoming conference.\n\
+    spam,Make millions from home! Our proven system guarantees success!!!\n\
+    ham,Your monthly bank statement is now available for review.\n\
+    spam,ATTENTION: Your computer is infected. Click here to fix immediately!\n\
+    ham,Draft proposal for the new marketing strategy is ready for your feedback.\n\
+    spam,Exclusive offer: Become a millionaire overnight! No investment required!\n\
+    ham,Reminder: Performance review meetings are scheduled for next week.\n\
+    spam,WIN BIG! Mega casino bonus waiting for you. No deposit needed!\n\
+    ham,Invoice #1234 for services rendered is attached for your records.\n";
+
+// Create a default dataset if none exists
+pub fn create_default_dataset(file_path: impl AsRef<Path>) -> Result<(), SpamError> {
+    let file_path = file_path.as_ref();
+    fs::write(file_path, DEFAULT_DATASET)?;
+    log::info!("Created default spam dataset: {}", file_path.display());
+    Ok(())
+}
+
+// What to do with a row whose label is not a recognized spelling of spam or ham
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidLabelPolicy {
+    // Stop loading and return SpamError::InvalidLabel
+    #[default]
+    Error,
+    // Log the row as a warning and leave it out of the dataset
+    Skip,
+}
+
+// Options controlling how a CSV dataset is read
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    pub invalid_labels: InvalidLabelPolicy,
+    // Header names of the label and content columns, matched case-insensitively;
+    // when a name occurs more than once the first such column is used. A column
+    // whose name the header lacks is read from its position below instead.
+    pub label_column: String,
+    pub content_column: String,
+    // Whether the first row is a header. Without one, every row is a record and
+    // the columns are always found by position.
+    pub has_headers: bool,
+    // Positions of the label and content columns, counted from 0
+    pub label_index: usize,
+    pub content_index: usize,
+    pub delimiter: u8,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            invalid_labels: InvalidLabelPolicy::default(),
+            label_column: "label".to_string(),
+            content_column: "content".to_string(),
+            has_headers: true,
+            label_index: 0,
+            content_index: 1,
+            delimiter: b',',
+        }
+    }
+}
+
+// Map the accepted label spellings (spam/ham in any case, or 1/0) to "spam" or "ham"
+pub fn normalize_label(label: &str) -> Option<&'static str> {
+    match label.trim().to_lowercase().as_str() {
+        "spam" | "1" => Some("spam"),
+        "ham" | "0" => Some("ham"),
+        _ => None,
+    }
+}
+
+// Load data from CSV
+pub fn load_data(file_path: impl AsRef<Path>) -> Result<Dataset, SpamError> {
+    load_data_with(file_path, &LoadOptions::default())
+}
+
+// Load data from CSV using the given options. Gzip-compressed files are
+// recognized by their magic bytes and decompressed on the fly.
+pub fn load_data_with(
+    file_path: impl AsRef<Path>,
+    options: &LoadOptions,
+) -> Result<Dataset, SpamError> {
+    load_data_from_reader_with(File::open(file_path)?, options)
+}
+
+// Load CSV data from any reader, such as a byte slice or an open socket, without
+// touching the filesystem. Gzip-compressed input is decompressed like in load_data.
+pub fn load_data_from_reader<R: Read>(reader: R) -> Result<Dataset, SpamError> {
+    load_data_from_reader_with(reader, &LoadOptions::default())
+}
+
+// load_data_from_reader using the given options
+pub fn load_data_from_reader_with<R: Read>(
+    reader: R,
+    options: &LoadOptions,
+) -> Result<Dataset, SpamError> {
+    let mut emails: Dataset = Vec::new();
+    for_each_record_in(decompressed(reader)?, options, |label, content| {
+        emails.push((label.to_string(), content.to_string()));
+        Ok(())
+    })?;
+    Ok(emails)
+}
+
+// Read a CSV dataset one record at a time, passing each normalized label and its
+// content to `handle` without keeping earlier records in memory. Returns the
+// number of records handled.
+pub fn for_each_record<F>(path: &Path, options: &LoadOptions, handle: F) -> Result<usize, SpamError>
+where
+    F: FnMut(&str, &str) -> Result<(), SpamError>,
+{
+    for_each_record_in(decompressed(File::open(path)?)?, options, handle)
+}
+
+fn for_each_record_in<R, F>(
+    reader: R,
+    options: &LoadOptions,
+    mut handle: F,
+) -> Result<usize, SpamError>
+where
+    R: Read,
+    F: FnMut(&str, &str) -> Result<(), SpamError>,
+{
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(options.has_headers)
+        .delimiter(options.delimiter)
+        .from_reader(reader);
+
+    let (label_index, content_index) = column_indexes(&mut rdr, options)?;
+
+    let mut handled = 0;
+    let mut record = csv::StringRecord::new();
+    let mut row = 0;
+    while rdr.read_record(&mut record)? {
+        row += 1;
+        let raw_label = record.get(label_index).unwrap_or("");
+        let content = record.get(content_index).unwrap_or("");
+
+        let Some(label) = normalize_label(raw_label) else {
+            match options.invalid_labels {
+                InvalidLabelPolicy::Error => {
+                    return Err(SpamError::InvalidLabel(raw_label.to_string()))
+                }
+                InvalidLabelPolicy::Skip => {
+                    log::warn!("Skipping row {}: invalid label {:?}", row, raw_label);
+                    continue;
+                }
+            }
+        };
+
+        handle(label, content)?;
+        handled += 1;
+    }
+
+    Ok(handled)
+}
+
+// Row counts from scanning a dataset with validate_dataset. Each bad row is
+// counted once, under the first problem found in the order of the fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub valid: usize,
+    // Rows too short to have both a label and a content column
+    pub missing_columns: usize,
+    pub invalid_labels: usize,
+    // Rows whose content is empty or only whitespace
+    pub empty_content: usize,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> usize {
+        self.missing_columns + self.invalid_labels + self.empty_content
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors() == 0
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Valid records:   {}", self.valid)?;
+        writeln!(f, "Missing columns: {}", self.missing_columns)?;
+        writeln!(f, "Invalid labels:  {}", self.invalid_labels)?;
+        write!(f, "Empty content:   {}", self.empty_content)
+    }
+}
+
+// Scan a CSV dataset without training and count its valid and malformed rows.
+// Unlike load_data, rows of the wrong length are counted rather than rejected.
+pub fn validate_dataset(path: &Path, options: &LoadOptions) -> Result<ValidationReport, SpamError> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(options.has_headers)
+        .flexible(true)
+        .delimiter(options.delimiter)
+        .from_reader(decompressed(File::open(path)?)?);
+    let (label_index, content_index) = column_indexes(&mut rdr, options)?;
+
+    let mut report = ValidationReport::default();
+    let mut record = csv::StringRecord::new();
+    while rdr.read_record(&mut record)? {
+        match (record.get(label_index), record.get(content_index)) {
+            (Some(label), Some(content)) => {
+                if normalize_label(label).is_none() {
+                    report.invalid_labels += 1;
+                } else if content.trim().is_empty() {
+                    report.empty_content += 1;
+                } else {
+                    report.valid += 1;
+                }
+            }
+            _ => report.missing_columns += 1,
+        }
+    }
+    Ok(report)
+}
+
+// Positions of the label and content columns: found by name in the header when
+// there is one, otherwise the positions in the options. Falling back to a
+// position in a header of more than two columns is logged, since the column
+// there may well hold something else. Fails when both resolve to one column.
+fn column_indexes<R: Read>(
+    rdr: &mut csv::Reader<R>,
+    options: &LoadOptions,
+) -> Result<(usize, usize), SpamError> {
+    let headers = if options.has_headers {
+        Some(rdr.headers()?)
+    } else {
+        None
+    };
+    let find_column = |name: &str, fallback: usize| {
+        let Some(headers) = headers else {
+            return fallback;
+        };
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .unwrap_or_else(|| {
+                if headers.len() > 2 {
+                    log::warn!(
+                        "Header has no {:?} column; reading it from column {}",
+                        name,
+                        fallback
+                    );
+                }
+                fallback
+            })
+    };
+
+    let label_index = find_column(&options.label_column, options.label_index);
+    let content_index = find_column(&options.content_column, options.content_index);
+    if label_index == content_index {
+        return Err(SpamError::InvalidSetting(format!(
+            "label and content would both be read from column {}",
+            label_index
+        )));
+    }
+    Ok((label_index, content_index))
+}
+
+// Wrap dataset input for reading, transparently decompressing gzip
+fn decompressed<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>, SpamError> {
+    let mut reader = BufReader::new(reader);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+// Load and concatenate several CSV datasets, each with its own header.
+// Files that don't exist are skipped with a logged warning.
+pub fn load_data_many(paths: &[&Path]) -> Result<Dataset, SpamError> {
+    let mut emails = Dataset::new();
+    for path in paths {
+        if !path.exists() {
+            log::warn!("Skipping missing dataset {}", path.display());
+            continue;
+        }
+        emails.extend(load_data(path)?);
+    }
+    Ok(emails)
+}
+
+// Append one labeled example to a CSV dataset as a single properly quoted record.
+// A missing file is created with a label,content header first, and a file whose
+// last line lacks a newline gets one before the new record.
+pub fn append_example(path: &Path, label: &str, content: &str) -> Result<(), SpamError> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+
+    let mut record = Vec::new();
+    let needs_header = file.metadata()?.len() == 0;
+    if !needs_header {
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            record.push(b'\n');
+        }
+    }
+
+    let mut wtr = csv::WriterBuilder::new().from_writer(record);
+    if needs_header {
+        wtr.write_record(["label", "content"])?;
+    }
+    wtr.write_record([label, content])?;
+    let record = wtr.into_inner().map_err(|e| e.into_error())?;
+
+    // One write in append mode so a concurrent reader never sees a partial row
+    file.write_all(&record)?;
+    Ok(())
+}
+
+// Hex SHA-256 of the dataset's records, independent of their order, so a saved
+// model can tell whether the data it was trained on has changed
+pub fn dataset_fingerprint(emails: &[(String, String)]) -> String {
+    let mut records: Vec<&(String, String)> = emails.iter().collect();
+    records.sort();
+
+    let mut hasher = Sha256::new();
+    for (label, content) in records {
+        // Length prefixes keep ("a", "bc") and ("ab", "c") distinct
+        for field in [label, content] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// Shuffle the emails with a seeded RNG and split off `test_ratio` of them as a test set.
+// The split depends only on the seed, so the same seed always gives the same split.
+pub fn split_dataset(
+    emails: &[(String, String)],
+    test_ratio: f64,
+    seed: u64,
+) -> (Dataset, Dataset) {
+    let mut shuffled = emails.to_vec();
+    let mut rng = StdRng::seed_from_u64(seed);
+    shuffled.shuffle(&mut rng);
+
+    let test_size = (shuffled.len() as f64 * test_ratio.clamp(0.0, 1.0)).round() as usize;
+    let train = shuffled.split_off(test_size);
+    (train, shuffled)
+}
+
+// Shuffle the emails with a seeded RNG and partition them into k folds of
+// near-equal size. Fails when k is below 2 or larger than the number of emails.
+pub fn k_folds(
+    emails: &[(String, String)],
+    k: usize,
+    seed: u64,
+) -> Result<Vec<Dataset>, SpamError> {
+    if k < 2 || k > emails.len() {
+        return Err(SpamError::InvalidSetting(format!(
+            "cannot split {} emails into {} folds; use between 2 and {} folds",
+            emails.len(),
+            k,
+            emails.len()
+        )));
+    }
+
+    let mut shuffled = emails.to_vec();
+    let mut rng = StdRng::seed_from_u64(seed);
+    shuffled.shuffle(&mut rng);
+
+    let n = shuffled.len();
+    let mut folds = Vec::with_capacity(k);
+    for i in (0..k).rev() {
+        folds.push(shuffled.split_off(i * n / k));
+    }
+    folds.reverse();
+    Ok(folds)
+}
+
+// How balance_classes equalizes the number of emails per label
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BalanceStrategy {
+    // Keep a random subset of every larger class, down to the size of the smallest
+    #[default]
+    Undersample,
+    // Repeat randomly drawn emails of every smaller class, up to the size of the largest
+    Oversample,
+}
+
+// Resample the emails so every label has the same count, then shuffle them, so a
+// skewed corpus (e.g. 95% ham) does not skew the class priors. The result depends
+// only on the seed; a dataset with a single label is returned shuffled.
+pub fn balance_classes(
+    emails: &[(String, String)],
+    strategy: BalanceStrategy,
+    seed: u64,
+) -> Dataset {
+    let mut classes: BTreeMap<&str, Vec<&(String, String)>> = BTreeMap::new();
+    for email in emails {
+        classes.entry(email.0.as_str()).or_default().push(email);
+    }
+
+    let sizes = classes.values().map(Vec::len);
+    let target = match strategy {
+        BalanceStrategy::Undersample => sizes.min(),
+        BalanceStrategy::Oversample => sizes.max(),
+    }
+    .unwrap_or(0);
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut balanced = Dataset::with_capacity(target * classes.len());
+    for members in classes.values_mut() {
+        members.shuffle(&mut rng);
+        balanced.extend(members.iter().take(target).map(|&email| email.clone()));
+        for _ in members.len()..target {
+            balanced.push(members[rng.gen_range(0..members.len())].clone());
+        }
+    }
+    balanced.shuffle(&mut rng);
+    balanced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    fn emails(rows: &[(&str, &str)]) -> Dataset {
+        rows.iter()
+            .map(|(label, content)| (label.to_string(), content.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn fingerprint_ignores_order_but_not_content() {
+        let original = emails(&[("spam", "free prize"), ("ham", "meeting notes")]);
+        let reordered = emails(&[("ham", "meeting notes"), ("spam", "free prize")]);
+        let edited = emails(&[("spam", "free prizes"), ("ham", "meeting notes")]);
+        let relabeled = emails(&[("ham", "free prize"), ("ham", "meeting notes")]);
+
+        let fingerprint = dataset_fingerprint(&original);
+        assert_eq!(fingerprint, dataset_fingerprint(&reordered));
+        assert_ne!(fingerprint, dataset_fingerprint(&edited));
+        assert_ne!(fingerprint, dataset_fingerprint(&relabeled));
+    }
+
+    #[test]
+    fn labels_are_normalized_or_rejected() {
+        for (raw, label) in [
+            ("spam", "spam"),
+            ("Spam", "spam"),
+            ("SPAM", "spam"),
+            ("1", "spam"),
+            ("ham", "ham"),
+            ("HAM", "ham"),
+            (" Ham ", "ham"),
+            ("0", "ham"),
+        ] {
+            assert_eq!(normalize_label(raw), Some(label), "{:?}", raw);
+        }
+        assert_eq!(normalize_label("sapm"), None);
+
+        let csv = "label,content\nSPAM,win now\n0,see you\nsapm,typo\n";
+        assert!(matches!(
+            load_data_from_reader(csv.as_bytes()),
+            Err(SpamError::InvalidLabel(label)) if label == "sapm"
+        ));
+        let options = LoadOptions {
+            invalid_labels: InvalidLabelPolicy::Skip,
+            ..LoadOptions::default()
+        };
+        let loaded = load_data_from_reader_with(csv.as_bytes(), &options).unwrap();
+        assert_eq!(loaded, emails(&[("spam", "win now"), ("ham", "see you")]));
+    }
+
+    #[test]
+    fn columns_are_found_by_name_with_any_delimiter() {
+        let options = LoadOptions {
+            label_column: "category".to_string(),
+            content_column: "text".to_string(),
+            ..LoadOptions::default()
+        };
+        let reordered = "id,text,category\n7,win a prize,spam\n8,lunch at noon,ham\n";
+        let expected = emails(&[("spam", "win a prize"), ("ham", "lunch at noon")]);
+        assert_eq!(
+            load_data_from_reader_with(reordered.as_bytes(), &options).unwrap(),
+            expected
+        );
+
+        let semicolons = LoadOptions {
+            delimiter: b';',
+            ..options
+        };
+        let csv = "category;text\nspam;win a prize\nham;lunch at noon\n";
+        assert_eq!(
+            load_data_from_reader_with(csv.as_bytes(), &semicolons).unwrap(),
+            expected
+        );
+
+        // Without matching names the columns are taken by position
+        let unnamed = "kind,body\nspam,win a prize\nham,lunch at noon\n";
+        assert_eq!(
+            load_data_from_reader_with(unnamed.as_bytes(), &LoadOptions::default()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn quoted_fields_keep_commas_and_newlines() {
+        let body = "Dear winner,\nclaim your $10,000 prize, today!\n\nRegards";
+        let csv = format!("label,content\nspam,\"{}\"\nham,plain line\n", body);
+        let loaded = load_data_from_reader(csv.as_bytes()).unwrap();
+        assert_eq!(loaded, emails(&[("spam", body), ("ham", "plain line")]));
+
+        let dir = scratch_dir("default-quoted");
+        let path = dir.join("default-quoted.csv");
+        create_default_dataset(&path).unwrap();
+        let defaults = load_data(&path).unwrap();
+        assert_eq!(defaults.len(), 20);
+        assert!(defaults
+            .iter()
+            .any(|(_, content)| content.contains("Claim your $10,000 prize NOW!")));
+    }
+
+    #[test]
+    fn load_data_many_concatenates_and_skips_missing_files() {
+        let dir = scratch_dir("load-many");
+        let january = dir.join("january.csv");
+        let february = dir.join("february.csv");
+        fs::write(&january, "label,content\nspam,win now\nham,see you\n").unwrap();
+        fs::write(&february, "content,label\nlunch?,ham\n").unwrap();
+        let missing = dir.join("march.csv");
+
+        let loaded = load_data_many(&[&january, &missing, &february]).unwrap();
+        assert_eq!(
+            loaded,
+            emails(&[("spam", "win now"), ("ham", "see you"), ("ham", "lunch?")])
+        );
+    }
+
+    #[test]
+    fn gzipped_datasets_load_like_plain_ones() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let dir = scratch_dir("gzip");
+        let plain = dir.join("gzip-source.csv");
+        create_default_dataset(&plain).unwrap();
+        let compressed = dir.join("gzip-source.csv.gz");
+        let mut encoder =
+            GzEncoder::new(File::create(&compressed).unwrap(), Compression::default());
+        encoder.write_all(&fs::read(&plain).unwrap()).unwrap();
+        encoder.finish().unwrap();
+
+        assert_eq!(load_data(&compressed).unwrap(), load_data(&plain).unwrap());
+    }
+
+    #[test]
+    fn appended_examples_reload_intact() {
+        let dir = scratch_dir("append");
+        let path = dir.join("appended.csv");
+
+        let tricky = "Hi, \"friend\", you won $1,000\non a second line";
+        append_example(&path, "spam", tricky).unwrap();
+        append_example(&path, "ham", "see you at lunch").unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("label,content\n"));
+        assert_eq!(
+            load_data(&path).unwrap(),
+            emails(&[("spam", tricky), ("ham", "see you at lunch")])
+        );
+
+        // A last line without a newline is terminated before the new record
+        fs::write(&path, "label,content\nham,no newline").unwrap();
+        append_example(&path, "spam", "win now").unwrap();
+        assert_eq!(
+            load_data(&path).unwrap(),
+            emails(&[("ham", "no newline"), ("spam", "win now")])
+        );
+    }
+
+    #[test]
+    fn validate_counts_each_kind_of_broken_row() {
+        let dir = scratch_dir("validate");
+        let path = dir.join("broken.csv");
+        fs::write(
+            &path,
+            "label,content\n\
+             spam,win a prize\n\
+             ham,see you at lunch\n\
+             lonely\n\
+             sapm,typo in the label\n\
+             ham,   \n\
+             spam,claim now\n",
+        )
+        .unwrap();
+
+        let report = validate_dataset(&path, &LoadOptions::default()).unwrap();
+        assert_eq!(
+            report,
+            ValidationReport {
+                valid: 3,
+                missing_columns: 1,
+                invalid_labels: 1,
+                empty_content: 1,
+            }
+        );
+        assert_eq!(report.errors(), 3);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn balanced_classes_are_equal_and_reproducible() {
+        let skewed: Dataset = (0..100)
+            .map(|i| {
+                let label = if i % 20 == 0 { "spam" } else { "ham" };
+                (label.to_string(), format!("email number {}", i))
+            })
+            .collect();
+        let count =
+            |emails: &Dataset, label: &str| emails.iter().filter(|(l, _)| l == label).count();
+
+        for (strategy, size) in [
+            (BalanceStrategy::Undersample, 5),
+            (BalanceStrategy::Oversample, 95),
+        ] {
+            let balanced = balance_classes(&skewed, strategy, 7);
+            assert_eq!(count(&balanced, "spam"), size, "{:?}", strategy);
+            assert_eq!(count(&balanced, "ham"), size, "{:?}", strategy);
+            assert!(balanced.iter().all(|email| skewed.contains(email)));
+            assert_eq!(balanced, balance_classes(&skewed, strategy, 7));
+            assert_ne!(balanced, balance_classes(&skewed, strategy, 8));
+        }
+    }
+
+    #[test]
+    fn four_column_files_yield_the_label_and_content() {
+        let expected = emails(&[("spam", "win a prize"), ("ham", "lunch at noon")]);
+        let headed = "timestamp,sender,label,content\n\
+                      2024-01-02,promo@example.com,spam,win a prize\n\
+                      2024-01-03,alice@example.com,ham,lunch at noon\n";
+        assert_eq!(load_data_from_reader(headed.as_bytes()).unwrap(), expected);
+
+        // Headerless files are read by the positions in the options
+        let headerless = LoadOptions {
+            has_headers: false,
+            label_index: 2,
+            content_index: 3,
+            ..LoadOptions::default()
+        };
+        let rows = "2024-01-02,promo@example.com,spam,win a prize\n\
+                    2024-01-03,alice@example.com,ham,lunch at noon\n";
+        assert_eq!(
+            load_data_from_reader_with(rows.as_bytes(), &headerless).unwrap(),
+            expected
+        );
+
+        // Pointing both columns at the same field is an error, not a silent misread
+        let ambiguous = LoadOptions {
+            content_index: 2,
+            ..headerless
+        };
+        assert!(matches!(
+            load_data_from_reader_with(rows.as_bytes(), &ambiguous),
+            Err(SpamError::InvalidSetting(_))
+        ));
+    }
+}
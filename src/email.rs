@@ -0,0 +1,303 @@
+use crate::data::{normalize_label, Dataset};
+use crate::error::SpamError;
+use std::fs;
+use std::path::Path;
+
+// The parts of a raw RFC 822 message the classifier cares about
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedEmail {
+    pub subject: String,
+    pub from: String,
+    pub body: String,
+}
+
+impl ParsedEmail {
+    // Subject and body joined into the single text used for training
+    pub fn text(&self) -> String {
+        match (self.subject.is_empty(), self.body.is_empty()) {
+            (true, _) => self.body.clone(),
+            (false, true) => self.subject.clone(),
+            (false, false) => format!("{}\n{}", self.subject, self.body),
+        }
+    }
+
+    // The body split into the message and its signature at the last "-- "
+    // delimiter line (a bare "--" is accepted too). The signature is empty when the
+    // body has no such line.
+    pub fn body_and_signature(&self) -> (&str, &str) {
+        let mut start = 0;
+        let mut delimiter = None;
+        for line in self.body.split_inclusive('\n') {
+            if line.trim_end_matches(['\r', '\n']).trim_end() == "--" {
+                delimiter = Some((start, start + line.len()));
+            }
+            start += line.len();
+        }
+        match delimiter {
+            Some((line_start, line_end)) => (
+                self.body[..line_start].trim_end(),
+                self.body[line_end..].trim(),
+            ),
+            None => (self.body.as_str(), ""),
+        }
+    }
+
+    // Lowercase domain of the From address, e.g. "example.com" for
+    // "Jane <jane@Example.com>". None when the header holds no address.
+    pub fn sender_domain(&self) -> Option<String> {
+        let address = match (self.from.rfind('<'), self.from.rfind('>')) {
+            (Some(start), Some(end)) if start < end => &self.from[start + 1..end],
+            _ => self.from.as_str(),
+        };
+        let (_, domain) = address.trim().rsplit_once('@')?;
+        let domain = domain.trim_end_matches('.');
+        if domain.is_empty() || domain.contains(char::is_whitespace) {
+            None
+        } else {
+            Some(domain.to_lowercase())
+        }
+    }
+}
+
+// Split a raw message into headers and body at the first blank line and pick out
+// the Subject and From headers. Folded header lines are unfolded. A message with
+// no blank line is treated as headers only, with an empty body.
+pub fn parse_email(raw: &str) -> ParsedEmail {
+    let mut email = ParsedEmail::default();
+    let mut lines = raw.lines();
+    let mut current: Option<(String, String)> = None;
+
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            // Continuation of the previous header
+            if let Some((_, value)) = current.as_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+        if let Some((name, value)) = current.take() {
+            store_header(&mut email, &name, value);
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            current = Some((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    if let Some((name, value)) = current {
+        store_header(&mut email, &name, value);
+    }
+
+    email.body = lines.collect::<Vec<&str>>().join("\n").trim().to_string();
+    email
+}
+
+fn store_header(email: &mut ParsedEmail, name: &str, value: String) {
+    if name.eq_ignore_ascii_case("subject") {
+        email.subject = value;
+    } else if name.eq_ignore_ascii_case("from") {
+        email.from = value;
+    }
+}
+
+// Read every .eml file in a directory as a record with the given label, with
+// subject and body joined into one text.
+// Files without a subject or body are skipped with a logged warning.
+pub fn load_eml_dir(dir: &Path, label: &str) -> Result<Dataset, SpamError> {
+    Ok(joined(load_eml_dir_parsed(dir, label)?))
+}
+
+// Like load_eml_dir but keeps subject and body as separate fields
+pub fn load_eml_dir_parsed(
+    dir: &Path,
+    label: &str,
+) -> Result<Vec<(String, ParsedEmail)>, SpamError> {
+    let label = normalize_label(label).ok_or_else(|| SpamError::InvalidLabel(label.to_string()))?;
+
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("eml"))
+        })
+        .collect();
+    paths.sort();
+
+    let mut emails = Vec::new();
+    for path in paths {
+        let raw = fs::read(&path)?;
+        let email = parse_email(&String::from_utf8_lossy(&raw));
+        if email.text().trim().is_empty() {
+            log::warn!("Skipping {}: no subject or body", path.display());
+            continue;
+        }
+        emails.push((label.to_string(), email));
+    }
+    Ok(emails)
+}
+
+// Split a Unix mbox into raw messages. A message starts at a "From " line that is
+// the first line of the file or follows a blank line, so "From" inside a body does
+// not split it. Quoted ">From " lines (any number of '>') lose one level of quoting.
+pub fn split_mbox(raw: &str) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    let mut previous_blank = true;
+
+    for line in raw.lines() {
+        if previous_blank && line.starts_with("From ") {
+            if let Some(lines) = current.take() {
+                messages.push(lines.join("\n"));
+            }
+            current = Some(Vec::new());
+        } else if let Some(lines) = current.as_mut() {
+            let unquoted_from = line.trim_start_matches('>').starts_with("From ");
+            lines.push(if unquoted_from && line.starts_with('>') {
+                &line[1..]
+            } else {
+                line
+            });
+        }
+        previous_blank = line.trim().is_empty();
+    }
+    if let Some(lines) = current {
+        messages.push(lines.join("\n"));
+    }
+
+    messages
+}
+
+// Read every message of an mbox file as a record with the given label, with
+// subject and body joined into one text.
+// Messages without a subject or body are skipped with a logged warning.
+pub fn load_mbox(path: &Path, label: &str) -> Result<Dataset, SpamError> {
+    Ok(joined(load_mbox_parsed(path, label)?))
+}
+
+// Like load_mbox but keeps subject and body as separate fields
+pub fn load_mbox_parsed(path: &Path, label: &str) -> Result<Vec<(String, ParsedEmail)>, SpamError> {
+    let label = normalize_label(label).ok_or_else(|| SpamError::InvalidLabel(label.to_string()))?;
+    let raw = fs::read(path)?;
+
+    let mut emails = Vec::new();
+    for (index, message) in split_mbox(&String::from_utf8_lossy(&raw))
+        .iter()
+        .enumerate()
+    {
+        let email = parse_email(message);
+        if email.text().trim().is_empty() {
+            log::warn!(
+                "Skipping message {} of {}: no subject or body",
+                index + 1,
+                path.display()
+            );
+            continue;
+        }
+        emails.push((label.to_string(), email));
+    }
+    Ok(emails)
+}
+
+fn joined(emails: Vec<(String, ParsedEmail)>) -> Dataset {
+    emails
+        .into_iter()
+        .map(|(label, email)| (label, email.text()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    #[test]
+    fn mbox_splits_only_on_separator_lines() {
+        let path = scratch_dir("mbox").join("two.mbox");
+        fs::write(
+            &path,
+            "From alice@example.com Mon Jan  1 00:00:00 2024\n\
+             Subject: Lunch\n\
+             \n\
+             See you at noon.\n\
+             From what I hear, the place is busy.\n\
+             >From the archive: bring cash.\n\
+             \n\
+             From bob@example.com Tue Jan  2 00:00:00 2024\n\
+             Subject: WIN NOW\n\
+             \n\
+             Claim your prize\n",
+        )
+        .unwrap();
+
+        let emails = load_mbox(&path, "ham").unwrap();
+        assert_eq!(emails.len(), 2);
+        assert_eq!(
+            emails[0],
+            (
+                "ham".to_string(),
+                "Lunch\nSee you at noon.\nFrom what I hear, the place is busy.\n\
+                 From the archive: bring cash."
+                    .to_string()
+            )
+        );
+        assert_eq!(emails[1].1, "WIN NOW\nClaim your prize");
+    }
+
+    #[test]
+    fn eml_directories_load_with_their_labels() {
+        let root = scratch_dir("eml");
+        let (spam, ham) = (root.join("spam"), root.join("ham"));
+        fs::create_dir_all(&spam).unwrap();
+        fs::create_dir_all(&ham).unwrap();
+        fs::write(
+            spam.join("1.eml"),
+            "From: Prize Desk <prizes@Lottery.example>\n\
+             Subject: You have won\n\
+             \tan exclusive prize\n\
+             \n\
+             Claim it now.\n\
+             Subject: not a header in the body\n",
+        )
+        .unwrap();
+        fs::write(spam.join("2.EML"), "Subject: free money\n").unwrap();
+        fs::write(
+            ham.join("1.eml"),
+            "From: alice@example.com\n\nLunch at noon?\n",
+        )
+        .unwrap();
+        // Files without a subject or body and files of other types are skipped
+        fs::write(
+            ham.join("empty.eml"),
+            "From: bob@example.com\nX-Note: nothing\n\n\n",
+        )
+        .unwrap();
+        fs::write(ham.join("notes.txt"), "Subject: not an email file\n\nHello").unwrap();
+
+        let spam_emails = load_eml_dir_parsed(&spam, "SPAM").unwrap();
+        assert_eq!(spam_emails.len(), 2);
+        let (label, folded) = &spam_emails[0];
+        assert_eq!(label, "spam");
+        assert_eq!(folded.subject, "You have won an exclusive prize");
+        assert_eq!(folded.from, "Prize Desk <prizes@Lottery.example>");
+        assert_eq!(
+            folded.body,
+            "Claim it now.\nSubject: not a header in the body"
+        );
+        assert_eq!(folded.sender_domain().as_deref(), Some("lottery.example"));
+        assert_eq!(spam_emails[1].1.subject, "free money");
+        assert_eq!(spam_emails[1].1.body, "");
+
+        assert_eq!(
+            load_eml_dir(&ham, "ham").unwrap(),
+            vec![("ham".to_string(), "Lunch at noon?".to_string())]
+        );
+        assert!(matches!(
+            load_eml_dir(&ham, "junk"),
+            Err(SpamError::InvalidLabel(_))
+        ));
+    }
+}
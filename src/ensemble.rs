@@ -0,0 +1,155 @@
+use crate::classifier::{Prediction, SpamClassifier, TOP_FEATURES};
+use crate::error::SpamError;
+use std::collections::HashMap;
+
+// Several classifiers voting on each message, for example a word-level model
+// and a character n-gram model that sees through obfuscated spellings. The spam
+// score of the ensemble is the weighted average of the members' spam scores.
+pub struct Ensemble {
+    members: Vec<(SpamClassifier, f64)>,
+    // Minimum weighted spam score for a message to be flagged as spam
+    threshold: f64,
+}
+
+impl Ensemble {
+    pub fn new() -> Self {
+        Ensemble {
+            members: Vec::new(),
+            threshold: 0.5,
+        }
+    }
+
+    // Add a trained classifier whose score counts `weight` times; weights are
+    // relative to each other and must be positive
+    pub fn add(&mut self, classifier: SpamClassifier, weight: f64) -> Result<(), SpamError> {
+        if !(weight.is_finite() && weight > 0.0) {
+            return Err(SpamError::InvalidSetting(format!(
+                "ensemble weight must be a positive number, got {}",
+                weight
+            )));
+        }
+        self.members.push((classifier, weight));
+        Ok(())
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn set_threshold(&mut self, threshold: f64) -> Result<(), SpamError> {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(SpamError::InvalidSetting(format!(
+                "threshold must be between 0.0 and 1.0, got {}",
+                threshold
+            )));
+        }
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    // Weighted average of the members' spam probabilities; 0.0 without members
+    pub fn predict_score(&self, message: &str) -> f64 {
+        let total_weight: f64 = self.members.iter().map(|(_, weight)| weight).sum();
+        if total_weight == 0.0 {
+            return 0.0;
+        }
+        self.members
+            .iter()
+            .map(|(classifier, weight)| weight * classifier.predict_score(message))
+            .sum::<f64>()
+            / total_weight
+    }
+
+    pub fn predict(&self, message: &str) -> bool {
+        self.predict_score(message) > self.threshold
+    }
+
+    // Classify the message with every member. The top features are the members'
+    // own, with each log-likelihood ratio weighted like the scores, so a token
+    // several members point to ranks higher.
+    pub fn classify(&self, message: &str) -> Prediction {
+        let total_weight: f64 = self.members.iter().map(|(_, weight)| weight).sum();
+        let mut score = 0.0;
+        let mut features: HashMap<String, f64> = HashMap::new();
+        for (classifier, weight) in &self.members {
+            let prediction = classifier.classify(message);
+            score += weight * prediction.score;
+            for (token, ratio) in prediction.top_features {
+                *features.entry(token).or_insert(0.0) += weight * ratio / total_weight;
+            }
+        }
+        if total_weight > 0.0 {
+            score /= total_weight;
+        }
+
+        let mut top_features: Vec<(String, f64)> = features.into_iter().collect();
+        top_features.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()).then(a.0.cmp(&b.0)));
+        top_features.truncate(TOP_FEATURES);
+        let is_spam = score > self.threshold;
+        Prediction {
+            is_spam,
+            score,
+            label: if is_spam { "spam" } else { "ham" }.to_string(),
+            top_features,
+        }
+    }
+}
+
+impl Default for Ensemble {
+    fn default() -> Self {
+        Ensemble::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trained(classifier: SpamClassifier) -> SpamClassifier {
+        let mut classifier = classifier;
+        let rows = [
+            ("spam", "free money waiting"),
+            ("spam", "get free money fast"),
+            ("ham", "the quarterly report is attached"),
+            ("ham", "notes from the team meeting"),
+        ];
+        let emails: Vec<(String, String)> = rows
+            .iter()
+            .map(|(label, content)| (label.to_string(), content.to_string()))
+            .collect();
+        classifier.train(&emails).unwrap();
+        classifier
+    }
+
+    #[test]
+    fn a_member_that_catches_a_message_outvotes_one_that_misses_it() {
+        let words = trained(SpamClassifier::new());
+        let grams = trained(SpamClassifier::builder().char_ngram(3).build().unwrap());
+        let obfuscated = "fr33 m0ney";
+        assert!(!words.predict(obfuscated));
+        assert!(grams.predict(obfuscated));
+        let expected =
+            (words.predict_score(obfuscated) + 3.0 * grams.predict_score(obfuscated)) / 4.0;
+
+        let mut ensemble = Ensemble::new();
+        ensemble.add(words, 1.0).unwrap();
+        ensemble.add(grams, 3.0).unwrap();
+        assert!((ensemble.predict_score(obfuscated) - expected).abs() < 1e-12);
+        assert!(ensemble.predict(obfuscated));
+        let prediction = ensemble.classify(obfuscated);
+        assert!(prediction.is_spam);
+        assert_eq!(prediction.label, "spam");
+        assert!(!ensemble.predict("notes from the quarterly meeting"));
+
+        assert!(ensemble.add(SpamClassifier::new(), 0.0).is_err());
+        assert_eq!(ensemble.len(), 2);
+    }
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// Failure modes of the spam detection library
+#[derive(Debug)]
+pub enum SpamError {
+    Io(io::Error),
+    Csv(csv::Error),
+    // The dataset contained no usable emails
+    EmptyDataset,
+    // The training data has no emails of this class
+    MissingClass(String),
+    // A prediction was requested from a model trained on no emails
+    Untrained,
+    // Scoring a message produced NaN or an infinite probability
+    NonFiniteScore(f64),
+    // A dataset label that is neither spam nor ham
+    InvalidLabel(String),
+    // A saved model that is corrupt, incompatible or could not be serialized
+    ModelParse(String),
+    // A classifier setting outside its allowed range
+    InvalidSetting(String),
+    // A config file that could not be parsed or holds an invalid setting
+    Config(String),
+    // An IMAP server that could not be reached, refused the login or dropped
+    // the connection
+    Imap(String),
+}
+
+impl fmt::Display for SpamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpamError::Io(e) => write!(f, "I/O error: {}", e),
+            SpamError::Csv(e) => write!(f, "CSV error: {}", e),
+            SpamError::EmptyDataset => write!(f, "Dataset contains no emails"),
+            SpamError::MissingClass(label) => {
+                write!(
+                    f,
+                    "Dataset contains no {} emails; at least two classes are needed",
+                    label
+                )
+            }
+            SpamError::Untrained => write!(f, "Model has not been trained on any emails"),
+            SpamError::NonFiniteScore(score) => {
+                write!(
+                    f,
+                    "Scoring produced a non-finite spam probability: {}",
+                    score
+                )
+            }
+            SpamError::InvalidLabel(label) => write!(f, "Invalid label: {:?}", label),
+            SpamError::ModelParse(msg) => write!(f, "Model error: {}", msg),
+            SpamError::InvalidSetting(msg) => write!(f, "Invalid setting: {}", msg),
+            SpamError::Config(msg) => write!(f, "Config error: {}", msg),
+            SpamError::Imap(msg) => write!(f, "IMAP error: {}", msg),
+        }
+    }
+}
+
+impl Error for SpamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpamError::Io(e) => Some(e),
+            SpamError::Csv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SpamError {
+    fn from(e: io::Error) -> Self {
+        SpamError::Io(e)
+    }
+}
+
+impl From<csv::Error> for SpamError {
+    fn from(e: csv::Error) -> Self {
+        SpamError::Csv(e)
+    }
+}
+
+impl From<serde_json::Error> for SpamError {
+    fn from(e: serde_json::Error) -> Self {
+        SpamError::ModelParse(e.to_string())
+    }
+}
@@ -0,0 +1,100 @@
+// Classify every message of an IMAP mailbox. The mailbox is opened with EXAMINE
+// and bodies are fetched with BODY.PEEK, so scanning never changes flags, moves
+// or deletes anything.
+use crate::classifier::SpamClassifier;
+use crate::email::parse_email;
+use crate::error::SpamError;
+use native_tls::TlsConnector;
+use serde::Serialize;
+
+// Messages requested per FETCH command, so a large mailbox is never held in
+// memory all at once
+const FETCH_BATCH: u32 = 100;
+
+// Where and as whom to connect; the connection always uses TLS
+#[derive(Debug, Clone)]
+pub struct ImapAccount {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+}
+
+// One classified message of a scanned mailbox. `message_id` is empty when the
+// message has no Message-ID header.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScannedMessage {
+    pub message_id: String,
+    pub subject: String,
+    pub label: String,
+    pub score: f64,
+}
+
+// Connect to the account, classify every message in `mailbox` in order and log
+// out. Failures to connect, to log in and a dropped connection are reported as
+// SpamError::Imap naming the host.
+pub fn scan_mailbox(
+    classifier: &SpamClassifier,
+    account: &ImapAccount,
+    mailbox: &str,
+) -> Result<Vec<ScannedMessage>, SpamError> {
+    let host = account.host.as_str();
+    let tls =
+        TlsConnector::new().map_err(|e| SpamError::Imap(format!("cannot set up TLS: {}", e)))?;
+    let client = imap::connect((host, account.port), host, &tls)
+        .map_err(|e| imap_error(host, "cannot connect", e))?;
+    let mut session = client
+        .login(&account.user, &account.password)
+        .map_err(|(e, _)| imap_error(host, &format!("login as {} failed", account.user), e))?;
+
+    let exists = session
+        .examine(mailbox)
+        .map_err(|e| imap_error(host, &format!("cannot open mailbox {}", mailbox), e))?
+        .exists;
+
+    let mut scanned = Vec::new();
+    let mut first = 1;
+    while first <= exists {
+        let last = (first + FETCH_BATCH - 1).min(exists);
+        let fetches = session
+            .fetch(format!("{}:{}", first, last), "(ENVELOPE BODY.PEEK[])")
+            .map_err(|e| imap_error(host, "fetching messages failed", e))?;
+        for fetch in fetches.iter() {
+            let raw = String::from_utf8_lossy(fetch.body().unwrap_or_default());
+            let email = parse_email(&raw);
+            let score = classifier.predict_score_parsed(&email);
+            let message_id = fetch
+                .envelope()
+                .and_then(|envelope| envelope.message_id)
+                .map(|id| String::from_utf8_lossy(id).into_owned())
+                .unwrap_or_default();
+            scanned.push(ScannedMessage {
+                message_id,
+                label: if classifier.predict_parsed(&email) {
+                    "spam"
+                } else {
+                    "ham"
+                }
+                .to_string(),
+                subject: email.subject,
+                score,
+            });
+        }
+        first = last + 1;
+    }
+
+    // The scan is complete even if the server drops the connection at logout
+    if let Err(e) = session.logout() {
+        log::warn!("{}", imap_error(host, "logout failed", e));
+    }
+    Ok(scanned)
+}
+
+fn imap_error(host: &str, context: &str, e: imap::Error) -> SpamError {
+    match e {
+        imap::Error::ConnectionLost => {
+            SpamError::Imap(format!("{}: {}: connection lost", host, context))
+        }
+        e => SpamError::Imap(format!("{}: {}: {}", host, context, e)),
+    }
+}
@@ -0,0 +1,7 @@
+// ISO 639-3 code (e.g. "eng", "fra") of the language a text is written in, or None
+// when the text is too short or mixed for whatlang to tell reliably
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code())
+}
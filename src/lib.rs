@@ -0,0 +1,52 @@
+// Email spam detection library: a Naive Bayes classifier and dataset loading helpers
+mod classifier;
+mod config;
+mod data;
+mod email;
+mod ensemble;
+mod error;
+#[cfg(feature = "imap")]
+mod imap_scan;
+mod language;
+mod metrics;
+#[cfg(feature = "http")]
+mod server;
+#[cfg(test)]
+mod test_util;
+mod tokenizer;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use classifier::{
+    ClassifierSnapshot, ModelSummary, ModelVariant, Prediction, ScoringMode, SpamClassifier,
+    SpamClassifierBuilder, TokenizedBatch, DEFAULT_SPAM_KEYWORDS, UNCERTAIN_LABEL,
+};
+pub use config::{Config, CONFIG_FILE_PATH};
+pub use data::{
+    append_example, balance_classes, create_default_dataset, dataset_fingerprint, for_each_record,
+    k_folds, load_data, load_data_from_reader, load_data_from_reader_with, load_data_many,
+    load_data_with, normalize_label, split_dataset, validate_dataset, BalanceStrategy, Dataset,
+    InvalidLabelPolicy, LoadOptions, ValidationReport,
+};
+pub use email::{
+    load_eml_dir, load_eml_dir_parsed, load_mbox, load_mbox_parsed, parse_email, split_mbox,
+    ParsedEmail,
+};
+pub use ensemble::Ensemble;
+pub use error::SpamError;
+#[cfg(feature = "imap")]
+pub use imap_scan::{scan_mailbox, ImapAccount, ScannedMessage};
+pub use language::detect_language;
+pub use metrics::{
+    cross_validate, cross_validate_with, precision_recall_curve, write_curve, ConfusionMatrix,
+    Metrics, MetricsSummary, ScoreHistogram,
+};
+#[cfg(feature = "http")]
+pub use server::serve;
+pub use tokenizer::{
+    add_ngrams, char_ngrams, collapse_repeats, default_stop_words, load_stop_words,
+    normalize_numbers, soundex, strip_html, structural_tokens, tokenize, tokenize_case_sensitive,
+    tokenize_with_urls, UrlFeatures, CAPS_HIGH_TOKEN, CAPS_SOME_TOKEN, CHAR_NGRAM_PREFIX,
+    DEFAULT_STOP_WORDS, EXCLAMATION_TOKEN, MONEY_TOKEN, NGRAM_SEPARATOR, NUM_TOKEN,
+    PHONETIC_PREFIX, URL_TOKEN,
+};
@@ -1,188 +1,1085 @@
-use csv::ReaderBuilder;
-use serde::{Deserialize, Serialize};
+use clap::{Parser, Subcommand, ValueEnum};
+use emailspamdetection::{
+    append_example, balance_classes, create_default_dataset, cross_validate_with,
+    dataset_fingerprint, load_data, precision_recall_curve, split_dataset, validate_dataset,
+    write_curve, BalanceStrategy, ClassifierSnapshot, Config, Dataset, LoadOptions, MetricsSummary,
+    SpamClassifier, CONFIG_FILE_PATH,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use owo_colors::{OwoColorize, Stream};
+use sha2::{Digest, Sha256};
 use std::error::Error;
 use std::fs;
-use std::path::Path;
-
-// Simple Naive Bayes Classifier for Spam Detection
-#[derive(Serialize, Deserialize)]
-struct SpamClassifier {
-    spam_words: Vec<String>,
-    ham_words: Vec<String>,
-    spam_count: usize,
-    ham_count: usize,
-}
-
-impl SpamClassifier {
-    fn new() -> Self {
-        SpamClassifier {
-            spam_words: Vec::new(),
-            ham_words: Vec::new(),
-            spam_count: 0,
-            ham_count: 0,
-        }
-    }
-
-    fn train(&mut self, emails: &Vec<(String, String)>) {
-        for (label, content) in emails {
-            let words: Vec<String> = content
-                .to_lowercase()
-                .split_whitespace()
-                .map(|s| s.to_string())
-                .collect();
-
-            if label == "spam" {
-                self.spam_words.extend(words);
-                self.spam_count += 1;
-            } else {
-                self.ham_words.extend(words);
-                self.ham_count += 1;
-            }
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+const MODEL_FILE_PATH: &str = "model.json";
+const DATA_FILE_PATH: &str = "emails.csv";
+// Typed on its own line to leave the classify-many-lines submode
+const QUIT_SENTINEL: &str = ":quit";
+// Seed for the held-out evaluation split, so repeated evaluations are comparable
+const EVAL_SEED: u64 = 42;
+// Environment variable scan-imap reads the password from, keeping it out of the
+// shell history and process list
+#[cfg(feature = "imap")]
+const IMAP_PASSWORD_VAR: &str = "SPAMDETECT_IMAP_PASSWORD";
+
+const EXIT_CODES: &str = "Exit codes:
+  0  success, or the message is ham (check, --stdin)
+  1  the message is spam (check, --stdin), or the dataset has bad rows (validate)
+  2  an error occurred";
+
+// Email spam detection; runs the interactive menu when no subcommand is given
+#[derive(Parser)]
+#[command(name = "spamdetect", about = "Email spam detection using Naive Bayes")]
+#[command(after_help = EXIT_CODES, disable_version_flag = true)]
+struct Cli {
+    /// Print the version, plus details of the saved model and dataset when present
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// Read one message from stdin, print SPAM or HAM and exit with 1 for spam, 0 for ham
+    #[arg(long)]
+    stdin: bool,
+
+    /// Show the words that contributed most to each interactive verdict
+    #[arg(long)]
+    explain: bool,
+
+    /// Output format for predictions; json prints one object per prediction
+    #[arg(long, value_enum, default_value_t = Format::Human, global = true)]
+    format: Format,
+
+    /// Retrain instead of only warning when the saved model is stale for its dataset
+    #[arg(long, global = true)]
+    retrain_if_stale: bool,
+
+    /// Read settings from this TOML file instead of spamdetect.toml (if present)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Where the model is saved and loaded [default: model.json, or `model` in the config]
+    #[arg(long, global = true)]
+    model: Option<PathBuf>,
+
+    /// Labeled CSV dataset with label,content columns [default: emails.csv, or `data` in the config]
+    #[arg(long, global = true)]
+    data: Option<PathBuf>,
+
+    /// Fail when the dataset is missing or holds no emails instead of writing a
+    /// sample dataset there
+    #[arg(long, global = true)]
+    no_autocreate: bool,
+
+    /// Spam threshold between 0.0 and 1.0, also applied to an already saved model
+    #[arg(long, global = true)]
+    threshold: Option<f64>,
+
+    /// Print only a lowercase spam/ham label per prediction and no status lines
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Never color verdicts (colors are also off when NO_COLOR is set or stdout is not a terminal)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Human,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Balance {
+    /// Drop random emails of the larger class
+    Undersample,
+    /// Repeat random emails of the smaller class
+    Oversample,
+}
+
+impl From<Balance> for BalanceStrategy {
+    fn from(balance: Balance) -> Self {
+        match balance {
+            Balance::Undersample => BalanceStrategy::Undersample,
+            Balance::Oversample => BalanceStrategy::Oversample,
         }
     }
+}
 
-    fn predict(&self, message: &str) -> bool {
-        let message_words: Vec<String> = message
-            .to_lowercase()
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
+#[derive(Subcommand)]
+enum KeywordAction {
+    /// Print every spam keyword (the default)
+    List,
+    /// Add a spam keyword or phrase
+    Add { keyword: String },
+    /// Remove a spam keyword or phrase
+    Remove { keyword: String },
+}
 
-        let spam_indicators = [
-            "free",
-            "win",
-            "urgent",
-            "lottery",
-            "click here",
-            "limited offer",
-            "$$$",
-            "winner",
-            "prize",
-            "congratulations",
-        ];
+#[derive(Subcommand)]
+enum Command {
+    /// Classify a single message, exiting with 1 for spam and 0 for ham
+    Check {
+        /// The message text to classify
+        #[arg(required_unless_present = "file")]
+        message: Option<String>,
+        /// Classify the entire contents of this file instead, e.g. a saved email
+        #[arg(long, conflicts_with = "message")]
+        file: Option<PathBuf>,
+    },
+    /// Train a new model from a CSV dataset and save it
+    Train {
+        /// Show training progress on stderr (a progress bar on a terminal, plain lines otherwise)
+        #[arg(long)]
+        progress: bool,
+        /// Pick the threshold that maximizes F1 on a held-out 20% before training on everything
+        #[arg(long)]
+        tune_threshold: bool,
+        /// Fit Platt scaling on a held-out 20% so reported probabilities are calibrated
+        #[arg(long)]
+        calibrate: bool,
+        /// Resample the dataset to equal spam and ham counts before training
+        #[arg(long, value_enum)]
+        balance: Option<Balance>,
+        /// Read the dataset one record at a time instead of loading it into memory.
+        /// The model then records no dataset fingerprint.
+        #[arg(long, conflicts_with_all = ["progress", "tune_threshold", "calibrate", "balance"])]
+        stream: bool,
+    },
+    /// Report accuracy metrics on a held-out 20% of the dataset
+    Eval {
+        /// Seed for shuffling the dataset before the train/test split
+        #[arg(long, default_value_t = EVAL_SEED)]
+        seed: u64,
+        /// Run k-fold cross-validation with this many folds instead of a single split
+        #[arg(long)]
+        folds: Option<usize>,
+        /// Also write the precision-recall curve of the held-out emails to this CSV
+        #[arg(long, conflicts_with = "folds")]
+        curve: Option<PathBuf>,
+        /// Also write the held-out emails the model got wrong to this CSV
+        #[arg(long, conflicts_with = "folds")]
+        misclassified: Option<PathBuf>,
+    },
+    /// Count the valid and malformed rows of the dataset without training,
+    /// exiting with 1 if any row is malformed
+    Validate,
+    /// Show the words the model considers the strongest spam and ham indicators
+    Indicators,
+    /// Print training statistics: email counts, class priors, vocabulary and top words
+    Summary,
+    /// List, add or remove the spam keywords that boost the score of messages
+    /// containing them; changes are saved to the model
+    Keywords {
+        #[command(subcommand)]
+        action: Option<KeywordAction>,
+    },
+    /// Add the counts of another model file, trained with the same tokenizer
+    /// settings, to the model and save it
+    Merge {
+        /// The model file to merge in
+        other: PathBuf,
+    },
+    /// Classify every message in a CSV file (newline-delimited JSON with --format json)
+    Classify {
+        /// CSV file of messages to classify
+        #[arg(long)]
+        input: PathBuf,
+        /// Where to write the content,label,score results
+        #[arg(long)]
+        output: PathBuf,
+        /// Finish with the number of messages, elapsed time and messages per second
+        #[arg(long)]
+        stats: bool,
+    },
+    /// Print how the spam probabilities of the dataset's emails, or of the
+    /// messages in a CSV file, are distributed as an ASCII bar chart
+    Histogram {
+        /// CSV file of messages to score instead of the dataset
+        #[arg(long)]
+        input: Option<PathBuf>,
+        /// Number of equal-width score ranges between 0.0 and 1.0
+        #[arg(long, default_value_t = 10)]
+        bins: usize,
+    },
+    /// Classify every message in an IMAP mailbox without changing it, reading the
+    /// password from the SPAMDETECT_IMAP_PASSWORD environment variable
+    #[cfg(feature = "imap")]
+    ScanImap {
+        /// IMAP server, connected to over TLS
+        #[arg(long)]
+        host: String,
+        #[arg(long, default_value_t = 993)]
+        port: u16,
+        /// Login name on the server
+        #[arg(long)]
+        user: String,
+        /// Mailbox to classify, opened read-only
+        #[arg(long, default_value = "INBOX")]
+        mailbox: String,
+    },
+    /// Answer POST /classify with {"message": "..."} by a JSON verdict over HTTP,
+    /// plus GET /health, until stopped
+    #[cfg(feature = "http")]
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        /// Address to listen on; use 0.0.0.0 to accept connections from other hosts
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+    },
+}
 
-        let spam_word_matches = message_words
-            .iter()
-            .filter(|word| spam_indicators.contains(&word.as_str()))
-            .count();
+fn main() -> ExitCode {
+    // Diagnostics go through `log`; warnings are shown unless RUST_LOG says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let cli = Cli::parse();
+    if cli.no_color {
+        owo_colors::set_override(false);
+    }
 
-        // Simple heuristic: More than 2 spam indicators suggests spam
-        spam_word_matches > 2
+    match run(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(2)
+        }
     }
+}
 
-    fn save_model(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
-        let json = serde_json::to_string(self)?;
-        fs::write(file_path, json)?;
-        println!("Model saved to {}", file_path);
-        Ok(())
+fn run(cli: Cli) -> Result<ExitCode, Box<dyn Error>> {
+    // Settings come from the config file, with command-line flags taking precedence
+    let mut config = match &cli.config {
+        Some(path) => Config::load(path)?,
+        None if Path::new(CONFIG_FILE_PATH).exists() => Config::load(Path::new(CONFIG_FILE_PATH))?,
+        None => Config::default(),
+    };
+    if cli.threshold.is_some() {
+        config.threshold = cli.threshold;
+        config.validate()?;
     }
+    let model_path = cli
+        .model
+        .clone()
+        .or_else(|| config.model.clone())
+        .unwrap_or_else(|| PathBuf::from(MODEL_FILE_PATH));
+    let data_path = cli
+        .data
+        .clone()
+        .or_else(|| config.data.clone())
+        .unwrap_or_else(|| PathBuf::from(DATA_FILE_PATH));
+    let model_file_path = model_path.as_path();
+    let data_file_path = data_path.as_path();
 
-    fn load_model(file_path: &str) -> Result<Self, Box<dyn Error>> {
-        let json = fs::read_to_string(file_path)?;
-        let classifier: SpamClassifier = serde_json::from_str(&json)?;
-        println!("Model loaded from {}", file_path);
-        Ok(classifier)
+    if cli.version {
+        print_version(model_file_path, data_file_path)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::Train {
+        progress,
+        tune_threshold,
+        calibrate,
+        balance,
+        stream,
+    }) = &cli.command
+    {
+        if *stream {
+            let mut classifier = retrained_classifier(&config, model_file_path)?;
+            let trained = classifier.train_streaming(data_file_path)?;
+            classifier.save(model_file_path)?;
+            if !cli.quiet {
+                println!("Trained on {} emails", trained);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let dataset = load_dataset(data_file_path)?;
+        // The fingerprint stays that of the file, so a balanced model still matches it
+        let fingerprint = dataset_fingerprint(&dataset);
+        let emails = match balance {
+            Some(balance) => balance_classes(&dataset, (*balance).into(), EVAL_SEED),
+            None => dataset,
+        };
+        let mut classifier = retrained_classifier(&config, model_file_path)?;
+        if *tune_threshold || *calibrate {
+            // Both are fitted on a model of the other 80%, then carried over to the
+            // model trained on everything; the threshold is tuned on calibrated scores
+            let (train, validation) = split_dataset(&emails, 0.2, EVAL_SEED);
+            let mut tuner = retrained_classifier(&config, model_file_path)?;
+            tuner.train(&train)?;
+            let validation = tuner.tokenize_batch(&validation);
+            if *calibrate {
+                tuner.calibrate_batch(&validation)?;
+                classifier.set_calibration(tuner.calibration());
+                if let (Some((a, b)), false) = (tuner.calibration(), cli.quiet) {
+                    println!("Calibration: a = {:.3}, b = {:.3}", a, b);
+                }
+            }
+            if *tune_threshold {
+                let threshold = tuner.tune_threshold_batch(&validation);
+                classifier.set_threshold(threshold)?;
+                if !cli.quiet {
+                    println!("Tuned threshold: {:.2}", threshold);
+                }
+            }
+        }
+        if *progress {
+            train_with_progress(&mut classifier, &emails)?;
+        } else {
+            classifier.train(&emails)?;
+        }
+        classifier.set_dataset_fingerprint(fingerprint);
+        classifier.save(model_file_path)?;
+        if !cli.quiet {
+            println!("Trained on {} emails", emails.len());
+        }
+        return Ok(ExitCode::SUCCESS);
     }
-}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let model_file_path = "spam_classifier_model.json";
-    let file_path = "emails.csv";
+    if let Some(Command::Eval {
+        seed,
+        folds,
+        curve,
+        misclassified,
+    }) = &cli.command
+    {
+        let emails = load_dataset(data_file_path)?;
+        match folds {
+            Some(k) => print_cross_validation(&emails, &config, *k, *seed)?,
+            None => print_evaluation(
+                &emails,
+                &config,
+                config.classifier()?.threshold(),
+                *seed,
+                curve.as_deref(),
+                misclassified.as_deref(),
+            )?,
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::Validate) = &cli.command {
+        let report = validate_dataset(data_file_path, &LoadOptions::default())?;
+        if !cli.quiet {
+            println!("{}:", data_file_path.display());
+            println!("{}", report);
+        }
+        return Ok(if report.is_valid() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::from(1)
+        });
+    }
 
     // Ensure dataset exists
-    if !Path::new(file_path).exists() {
-        create_default_dataset(file_path)?;
+    if !data_file_path.exists() {
+        if cli.no_autocreate {
+            return Err(format!(
+                "{} does not exist; pass --data with an existing dataset",
+                data_file_path.display()
+            )
+            .into());
+        }
+        create_default_dataset(data_file_path)?;
     }
 
-    // Load emails
-    let emails = load_data(file_path)?;
+    // Load emails. A file without records (zero bytes or only a header) would
+    // train a useless model, so it is replaced by the default dataset as well.
+    let mut emails = if cli.no_autocreate {
+        load_dataset(data_file_path)?
+    } else {
+        load_data(data_file_path)?
+    };
+    if emails.is_empty() {
+        log::warn!(
+            "{} contains no emails; replacing it with the default dataset",
+            data_file_path.display()
+        );
+        create_default_dataset(data_file_path)?;
+        emails = load_data(data_file_path)?;
+    }
 
     // Train classifier or load existing model
     let mut classifier: SpamClassifier;
 
-    if Path::new(model_file_path).exists() {
-        classifier = SpamClassifier::load_model(model_file_path)?;
+    if model_file_path.exists() {
+        classifier = SpamClassifier::load(model_file_path)?;
+        if let Some(threshold) = config.threshold {
+            classifier.set_threshold(threshold)?;
+        }
+        if classifier.is_stale(&emails) {
+            if cli.retrain_if_stale {
+                log::warn!(
+                    "{} changed since the model was trained; retraining",
+                    data_file_path.display()
+                );
+                classifier = retrained_classifier(&config, model_file_path)?;
+                classifier.train(&emails)?;
+                classifier.set_dataset_fingerprint(dataset_fingerprint(&emails));
+                classifier.save(model_file_path)?;
+            } else {
+                log::warn!(
+                    "{} changed since the model was trained; run with --retrain-if-stale or `spamdetect train` to update it",
+                    data_file_path.display()
+                );
+            }
+        }
     } else {
-        classifier = SpamClassifier::new();
-        classifier.train(&emails);
-        classifier.save_model(model_file_path)?; // Save the trained model
+        classifier = config.classifier()?;
+        classifier.train(&emails)?;
+        classifier.set_dataset_fingerprint(dataset_fingerprint(&emails));
+        classifier.save(model_file_path)?; // Save the trained model
+    }
+
+    match cli.command {
+        Some(Command::Check { message, file }) => {
+            let message = match file {
+                Some(path) => {
+                    let raw = fs::read(&path).map_err(|e| {
+                        format!("cannot read message file {}: {}", path.display(), e)
+                    })?;
+                    String::from_utf8_lossy(&raw).trim().to_string()
+                }
+                None => message.unwrap_or_default(),
+            };
+            return Ok(print_verdict(&classifier, &message, cli.format, cli.quiet));
+        }
+        Some(Command::Indicators) => {
+            print_indicators(&classifier, 20);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Summary) => {
+            println!("{}", classifier.summary());
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Keywords { action }) => {
+            match action.unwrap_or(KeywordAction::List) {
+                KeywordAction::List => {
+                    for keyword in classifier.spam_keywords() {
+                        println!("{}", keyword);
+                    }
+                }
+                KeywordAction::Add { keyword } => {
+                    if !classifier.add_spam_keyword(&keyword) {
+                        return Err(
+                            format!("{:?} is blank or already a spam keyword", keyword).into()
+                        );
+                    }
+                    classifier.save(model_file_path)?;
+                }
+                KeywordAction::Remove { keyword } => {
+                    if !classifier.remove_spam_keyword(&keyword) {
+                        return Err(format!("{:?} is not a spam keyword", keyword).into());
+                    }
+                    classifier.save(model_file_path)?;
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Merge { other }) => {
+            classifier.merge(&SpamClassifier::load(&other)?)?;
+            classifier.save(model_file_path)?;
+            if !cli.quiet {
+                println!(
+                    "Merged {} into {}",
+                    other.display(),
+                    model_file_path.display()
+                );
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Classify {
+            input,
+            output,
+            stats,
+        }) => {
+            let started = Instant::now();
+            let classified = match cli.format {
+                Format::Human => classifier.predict_file(&input, &output)?,
+                Format::Json => classifier.predict_file_json(&input, &output)?,
+            };
+            let elapsed = started.elapsed().as_secs_f64();
+            if !cli.quiet {
+                println!(
+                    "Classified {} messages into {}",
+                    classified,
+                    output.display()
+                );
+            }
+            if stats {
+                println!(
+                    "{} messages in {:.3}s ({:.0} messages/s)",
+                    classified,
+                    elapsed,
+                    classified as f64 / elapsed.max(f64::EPSILON)
+                );
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Histogram { input, bins }) => {
+            let histogram = match input {
+                Some(path) => classifier.score_histogram_file(&path, bins)?,
+                None => classifier.score_histogram(&emails, bins),
+            };
+            println!("{}", histogram);
+            if !cli.quiet {
+                println!(
+                    "{} messages scored; threshold {}",
+                    histogram.total(),
+                    classifier.threshold()
+                );
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        #[cfg(feature = "imap")]
+        Some(Command::ScanImap {
+            host,
+            port,
+            user,
+            mailbox,
+        }) => {
+            let password = std::env::var(IMAP_PASSWORD_VAR)
+                .map_err(|_| format!("set {} to the IMAP password", IMAP_PASSWORD_VAR))?;
+            let account = emailspamdetection::ImapAccount {
+                host,
+                port,
+                user,
+                password,
+            };
+            let scanned = emailspamdetection::scan_mailbox(&classifier, &account, &mailbox)?;
+            print_scan_report(&scanned, cli.format);
+            return Ok(ExitCode::SUCCESS);
+        }
+        #[cfg(feature = "http")]
+        Some(Command::Serve { port, host }) => {
+            let addr = format!("{}:{}", host, port);
+            if !cli.quiet {
+                println!("Serving on http://{}", addr);
+            }
+            emailspamdetection::serve(&classifier, &addr)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+
+    // Non-interactive mode: classify all of stdin
+    if cli.stdin {
+        let mut message = String::new();
+        io::stdin().read_to_string(&mut message)?;
+        return Ok(print_verdict(
+            &classifier,
+            message.trim(),
+            cli.format,
+            cli.quiet,
+        ));
     }
 
-    // Interactive mode
+    // Interactive mode. The model as it was before the last correction, and
+    // whether that correction was also appended to the dataset file.
+    let mut undo: Option<(ClassifierSnapshot, bool)> = None;
     loop {
         println!("\nSpam Detection Tool");
         println!("1. Check an email message");
         println!("2. Exit");
-        print!("Enter your choice (1/2): ");
-
-        let mut choice = String::new();
-        std::io::stdin().read_line(&mut choice)?;
+        println!(
+            "3. Set sensitivity threshold (current: {})",
+            classifier.threshold()
+        );
+        println!("4. Evaluate accuracy on a held-out 20% of the dataset");
+        println!("5. Show top indicators");
+        println!("6. Classify many lines (until {})", QUIT_SENTINEL);
+        println!("7. Add or remove a spam keyword");
+        println!("8. Show model summary");
+        println!("9. Undo the last correction");
+        let Some(choice) = prompt("Enter your choice (1-9): ")? else {
+            break;
+        };
 
         match choice.trim() {
             "1" => {
-                print!("Enter the email message to check for spam: ");
-                let mut input_message = String::new();
-                std::io::stdin().read_line(&mut input_message)?;
+                let Some(input_message) = prompt("Enter the email message to check for spam: ")?
+                else {
+                    break;
+                };
 
-                let result = classifier.predict(input_message.trim());
+                let message = input_message.trim();
+                let score = classifier.predict_score(message);
+                let is_spam = classifier.predict(message);
+                warn_language_mismatch(&classifier, message);
 
-                if result {
-                    println!("🚨 SPAM DETECTED! This message appears to be spam.");
+                if cli.format == Format::Json {
+                    println!("{}", classifier.prediction_json(message));
+                } else if is_spam {
+                    println!(
+                        "🚨 {} ({:.0}% confidence)! This message appears to be spam.",
+                        "SPAM DETECTED".if_supports_color(Stream::Stdout, |t| t.red()),
+                        score * 100.0
+                    );
                 } else {
-                    println!("✅ NO SPAM DETECTED. This message seems safe.");
+                    println!(
+                        "✅ {} ({:.0}% confidence). This message seems safe.",
+                        "NO SPAM DETECTED".if_supports_color(Stream::Stdout, |t| t.green()),
+                        (1.0 - score) * 100.0
+                    );
+                }
+
+                if cli.explain {
+                    print_explanation(&classifier, message);
+                }
+
+                // Feed corrections back into the model
+                let Some(correct) = prompt_yes("Was this correct? (y/n): ")? else {
+                    break;
+                };
+                if !correct {
+                    let corrected = if is_spam { "ham" } else { "spam" };
+                    undo = Some((classifier.snapshot(), false));
+                    classifier.update(corrected, message);
+                    classifier.save(model_file_path)?;
+                    println!(
+                        "Thanks! The model now treats this message as {}.",
+                        corrected
+                    );
+
+                    let Some(add) = prompt_yes(&format!(
+                        "Also add it to {}? (y/n): ",
+                        data_file_path.display()
+                    ))?
+                    else {
+                        break;
+                    };
+                    if add {
+                        append_example(data_file_path, corrected, message)?;
+                        emails.push((corrected.to_string(), message.to_string()));
+                        // The model already includes the correction, so it matches the new file
+                        classifier.set_dataset_fingerprint(dataset_fingerprint(&emails));
+                        classifier.save(model_file_path)?;
+                        if let Some((_, appended)) = &mut undo {
+                            *appended = true;
+                        }
+                        println!("Added to {}", data_file_path.display());
+                    }
                 }
             }
             "2" => break,
+            "3" => {
+                let Some(input_threshold) = prompt("Enter a threshold between 0.0 and 1.0: ")?
+                else {
+                    break;
+                };
+
+                match input_threshold.trim().parse::<f64>() {
+                    Ok(threshold) => match classifier.set_threshold(threshold) {
+                        Ok(()) => println!("Threshold set to {}", threshold),
+                        Err(e) => println!("{}", e),
+                    },
+                    Err(_) => println!("Invalid number. Please enter a value like 0.8."),
+                }
+            }
+            "4" => print_evaluation(
+                &emails,
+                &config,
+                classifier.threshold(),
+                EVAL_SEED,
+                None,
+                None,
+            )?,
+            "5" => print_indicators(&classifier, 20),
+            "6" => {
+                if !classify_lines(&classifier, cli.format)? {
+                    break;
+                }
+            }
+            "7" => {
+                if classifier.spam_keywords().is_empty() {
+                    println!("No spam keywords yet.");
+                } else {
+                    println!("Spam keywords: {}", classifier.spam_keywords().join(", "));
+                }
+                let Some(input_keyword) =
+                    prompt("Keyword to add, or an existing one to remove (blank to cancel): ")?
+                else {
+                    break;
+                };
+
+                let keyword = input_keyword.trim();
+                if keyword.is_empty() {
+                    continue;
+                }
+                if classifier.remove_spam_keyword(keyword) {
+                    println!("Removed spam keyword {:?}", keyword);
+                } else {
+                    classifier.add_spam_keyword(keyword);
+                    println!("Added spam keyword {:?}", keyword);
+                }
+                classifier.save(model_file_path)?;
+            }
+            "8" => println!("{}", classifier.summary()),
+            "9" => match undo.take() {
+                Some((snapshot, appended)) => {
+                    classifier.restore(snapshot);
+                    classifier.save(model_file_path)?;
+                    println!("Undid the last correction.");
+                    if appended {
+                        println!(
+                            "It is still in {}; remove its last line to drop it from the dataset.",
+                            data_file_path.display()
+                        );
+                    }
+                }
+                None => println!("No correction to undo."),
+            },
             _ => println!("Invalid choice. Please try again."),
         }
     }
 
+    // Reached by choosing Exit or by end of input (Ctrl-D) at any prompt
+    println!("\nGoodbye!");
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Classify each line typed or pasted, echoing its verdict right away, until a line
+// reading QUIT_SENTINEL. Blank lines are ignored. Ok(false) if stdin ends first.
+fn classify_lines(classifier: &SpamClassifier, format: Format) -> io::Result<bool> {
+    println!(
+        "Enter one message per line; {} returns to the menu.",
+        QUIT_SENTINEL
+    );
+    loop {
+        let Some(line) = prompt("> ")? else {
+            return Ok(false);
+        };
+        let message = line.trim();
+        if message == QUIT_SENTINEL {
+            return Ok(true);
+        }
+        if message.is_empty() {
+            continue;
+        }
+
+        let score = classifier.predict_score(message);
+        warn_language_mismatch(classifier, message);
+        if format == Format::Json {
+            println!("{}", classifier.prediction_json(message));
+        } else if classifier.predict(message) {
+            println!(
+                "  {} ({:.0}%)",
+                "SPAM".if_supports_color(Stream::Stdout, |t| t.red()),
+                score * 100.0
+            );
+        } else {
+            println!(
+                "  {} ({:.0}%)",
+                "HAM".if_supports_color(Stream::Stdout, |t| t.green()),
+                (1.0 - score) * 100.0
+            );
+        }
+    }
+}
+
+// Train while reporting progress on stderr: a progress bar on a terminal, and a
+// plain line at every tenth of the dataset when stderr is redirected. The flag
+// asks for the lines, so they are printed whatever the log level.
+fn train_with_progress(
+    classifier: &mut SpamClassifier,
+    emails: &[(String, String)],
+) -> Result<(), Box<dyn Error>> {
+    if io::stderr().is_terminal() {
+        let bar = ProgressBar::new(emails.len() as u64);
+        bar.set_style(ProgressStyle::with_template(
+            "Training {bar:40} {pos}/{len} emails ({eta})",
+        )?);
+        classifier.train_with_progress(emails, |done, _| bar.set_position(done as u64))?;
+        bar.finish_and_clear();
+    } else {
+        let mut reported = 0;
+        classifier.train_with_progress(emails, |done, total| {
+            let tenths = done * 10 / total;
+            if tenths > reported {
+                reported = tenths;
+                eprintln!("Trained {}/{} emails", done, total);
+            }
+        })?;
+    }
     Ok(())
 }
 
-// Create a default dataset if none exists
-fn create_default_dataset(file_path: &str) -> Result<(), Box<dyn Error>> {
-    let default_data = "label,content\n\
-        spam,Congratulations! You've won a free iPhone! Click here to claim now!!!\n\
-        ham,Hi John, can we schedule a meeting to discuss the project next week?\n\
-        spam,URGENT: You've been selected for an exclusive lottery. Claim your $10,000 prize NOW!\n\
-        ham,Please find attached the quarterly report for your review.\n\
-        spam,GET RICH QUICK! Make $5000 per week working from home. No experience needed!\n\
-        ham,Meeting minutes from today's team discussion are attached.\n\
-        spam,Limited time offer! 90% OFF all products. Buy now before it's gone!\n\
-        ham,Could you please send me the updated client contact list?\n\
-        spam,You are the WINNER of our mega sweepstakes! Claim your prize immediately!\n\
-        ham,Thank you for your recent order. Your package will be shipped soon.\n\
-        spam,FREE VIAGRA! Lowest prices guaranteed. Buy now!\n\
-        ham,Please confirm your attendance for the upcoming conference.\n\
-        spam,Make millions from home! Our proven system guarantees success!!!\n\
-        ham,Your monthly bank statement is now available for review.\n\
-        spam,ATTENTION: Your computer is infected. Click here to fix immediately!\n\
-        ham,Draft proposal for the new marketing strategy is ready for your feedback.\n\
-        spam,Exclusive offer: Become a millionaire overnight! No investment required!\n\
-        ham,Reminder: Performance review meetings are scheduled for next week.\n\
-        spam,WIN BIG! Mega casino bonus waiting for you. No deposit needed!\n\
-        ham,Invoice #1234 for services rendered is attached for your records.\n";
-
-    fs::write(file_path, default_data)?;
-    println!("Created default spam dataset: {}", file_path);
+// Print the crate version and, when they exist, the saved model's training
+// metadata and fingerprint and the dataset path, for support requests
+fn print_version(model_path: &Path, data_path: &Path) -> Result<(), Box<dyn Error>> {
+    println!("spamdetect {}", env!("CARGO_PKG_VERSION"));
+
+    if model_path.exists() {
+        println!("Model:       {}", model_path.display());
+        println!("Fingerprint: {}", file_fingerprint(model_path)?);
+        // An unusable model is part of what a support request needs to know, not an error
+        match SpamClassifier::load(model_path) {
+            Ok(classifier) => {
+                println!("Trained on:  {} emails", classifier.training_emails());
+                println!("Vocabulary:  {} words", classifier.vocabulary().len());
+                println!("Labels:      {}", classifier.labels().join(", "));
+                println!(
+                    "Language:    {}",
+                    classifier.dominant_language().unwrap_or("unknown")
+                );
+            }
+            Err(e) => println!("Unusable:    {}", e),
+        }
+    } else {
+        println!("Model:       none ({} not found)", model_path.display());
+    }
+
+    let dataset_state = if data_path.exists() {
+        "present"
+    } else {
+        "not found"
+    };
+    println!("Dataset:     {} ({})", data_path.display(), dataset_state);
     Ok(())
 }
 
-// Load data from CSV
-fn load_data(file_path: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(true)
-        .from_path(file_path)?;
+// First 16 hex digits of the SHA-256 of a file's contents
+fn file_fingerprint(path: &Path) -> io::Result<String> {
+    let digest = Sha256::digest(fs::read(path)?);
+    Ok(digest
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+// Print a prompt without a newline, flushing so it is visible before the user
+// types, then read one line from stdin. None once stdin has reached end of input.
+fn prompt(question: &str) -> io::Result<Option<String>> {
+    print!("{}", question);
+    io::stdout().flush()?;
+
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+// Ask a yes/no question on stdin; anything starting with "n" counts as no.
+// None at end of input.
+fn prompt_yes(question: &str) -> io::Result<Option<bool>> {
+    let answer = prompt(question)?;
+    Ok(answer.map(|answer| !answer.trim().to_lowercase().starts_with('n')))
+}
+
+// One line per scanned message: message-id, subject, label and score separated by
+// tabs, or one JSON object per message with --format json
+#[cfg(feature = "imap")]
+fn print_scan_report(scanned: &[emailspamdetection::ScannedMessage], format: Format) {
+    for message in scanned {
+        match format {
+            Format::Human => println!(
+                "{}\t{}\t{}\t{:.4}",
+                message.message_id, message.subject, message.label, message.score
+            ),
+            Format::Json => println!(
+                "{}",
+                serde_json::to_string(message).expect("scan results serialize to JSON")
+            ),
+        }
+    }
+}
+
+// Print SPAM or HAM for a message (just spam or ham when quiet) and turn the
+// verdict into the process exit code
+fn print_verdict(
+    classifier: &SpamClassifier,
+    message: &str,
+    format: Format,
+    quiet: bool,
+) -> ExitCode {
+    let is_spam = classifier.predict(message);
+    warn_language_mismatch(classifier, message);
+
+    match format {
+        _ if quiet => println!("{}", if is_spam { "spam" } else { "ham" }),
+        Format::Human if is_spam => {
+            println!("{}", "SPAM".if_supports_color(Stream::Stdout, |t| t.red()))
+        }
+        Format::Human => println!("{}", "HAM".if_supports_color(Stream::Stdout, |t| t.green())),
+        Format::Json => println!("{}", classifier.prediction_json(message)),
+    }
+
+    if is_spam {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// Log a warning when a message is not in the language the model was mostly trained on
+fn warn_language_mismatch(classifier: &SpamClassifier, message: &str) {
+    if let Some(language) = classifier.language_mismatch(message) {
+        log::warn!(
+            "Message looks like {} but the model was trained mostly on {}; the verdict may be unreliable",
+            language,
+            classifier.dominant_language().unwrap_or("unknown")
+        );
+    }
+}
+
+// Print the strongest spam signals in a message, e.g. "free (+2.1), win (+1.8)"
+fn print_explanation(classifier: &SpamClassifier, message: &str) {
+    let explanation = classifier.explain(message);
+    if explanation.is_empty() {
+        println!("No known words in this message; the verdict is based on the prior alone.");
+        return;
+    }
+
+    let signals: Vec<String> = explanation
+        .iter()
+        .filter(|(_, ratio)| *ratio > 0.0)
+        .take(5)
+        .map(|(word, ratio)| format!("{} ({:+.1})", word, ratio))
+        .collect();
+
+    if signals.is_empty() {
+        println!("No spam signals found.");
+    } else {
+        println!("Top spam signals: {}", signals.join(", "));
+    }
+}
+
+// Print the n strongest spam and ham indicator words
+fn print_indicators(classifier: &SpamClassifier, n: usize) {
+    println!("Top spam indicators:");
+    for (word, ratio) in classifier.top_spam_words(n) {
+        println!("  {:<20} {:+.2}", word, ratio);
+    }
 
-    let mut emails: Vec<(String, String)> = Vec::new();
+    println!("Top ham indicators:");
+    for (word, ratio) in classifier.top_ham_words(n) {
+        println!("  {:<20} {:+.2}", word, ratio);
+    }
+}
 
-    for result in rdr.records() {
-        let record = result?;
-        let label = record.get(0).unwrap_or("ham").to_string();
-        let content = record.get(1).unwrap_or("").to_string();
-        emails.push((label, content));
+// A classifier configured for retraining. Spam keywords added with `spamdetect
+// keywords` are stored only in the saved model, so unless the config lists its
+// own they are carried over from the model about to be replaced.
+fn retrained_classifier(
+    config: &Config,
+    model_path: &Path,
+) -> Result<SpamClassifier, Box<dyn Error>> {
+    let classifier = config.classifier()?;
+    if config.spam_keywords.is_some() || !model_path.exists() {
+        return Ok(classifier);
     }
+    match SpamClassifier::load(model_path) {
+        Ok(previous) => Ok(classifier.with_spam_keywords(previous.spam_keywords().to_vec())),
+        Err(e) => {
+            log::warn!(
+                "Cannot keep the spam keywords of {}: {}",
+                model_path.display(),
+                e
+            );
+            Ok(classifier)
+        }
+    }
+}
 
+// Load the dataset for train and eval, which never replace the user's file, with
+// an error naming the file when it holds no emails
+fn load_dataset(path: &Path) -> Result<Dataset, Box<dyn Error>> {
+    let emails = load_data(path)?;
+    if emails.is_empty() {
+        return Err(format!(
+            "{} contains no emails (the file is empty or has only a header row)",
+            path.display()
+        )
+        .into());
+    }
     Ok(emails)
 }
+
+// Train a classifier with the configured settings on 80% of the emails and print
+// metrics for the held-out 20%, optionally writing their precision-recall curve
+// to `curve_path` and the emails the model got wrong to `misclassified_path`.
+// The same seed always gives the same split and therefore the same metrics.
+fn print_evaluation(
+    emails: &[(String, String)],
+    config: &Config,
+    threshold: f64,
+    seed: u64,
+    curve_path: Option<&Path>,
+    misclassified_path: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let (train, test) = split_dataset(emails, 0.2, seed);
+    let mut evaluator = config.classifier()?;
+    evaluator.set_threshold(threshold)?;
+    evaluator.train(&train)?;
+
+    println!(
+        "Trained on {} emails, evaluated on {} held-out emails:",
+        train.len(),
+        test.len()
+    );
+    // Tokenized once for the metrics, the confusion matrix and the curve
+    let batch = evaluator.tokenize_batch(&test);
+    println!("{}", evaluator.evaluate_batch(&batch));
+    println!("\n{}", evaluator.confusion_matrix_batch(&batch));
+
+    if let Some(path) = curve_path {
+        let curve = precision_recall_curve(&evaluator.scored_labels_batch(&batch));
+        write_curve(path, &curve)?;
+        println!("\nWrote precision-recall curve to {}", path.display());
+    }
+
+    if let Some(path) = misclassified_path {
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record(["content", "label", "score"])?;
+        let mut written = 0;
+        for (content, label, score) in evaluator.misclassified(&test) {
+            wtr.write_record([content, label, &format!("{:.4}", score)])?;
+            written += 1;
+        }
+        wtr.flush()?;
+        println!(
+            "\nWrote {} misclassified emails to {}",
+            written,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+// Print the metrics of every fold of a k-fold cross-validation and their mean ± std dev
+fn print_cross_validation(
+    emails: &[(String, String)],
+    config: &Config,
+    k: usize,
+    seed: u64,
+) -> Result<(), Box<dyn Error>> {
+    let results = cross_validate_with(emails, k, seed, || config.classifier())?;
+
+    for (i, metrics) in results.iter().enumerate() {
+        println!(
+            "Fold {}: accuracy {:.1}%, precision {:.1}%, recall {:.1}%, F1 {:.3}",
+            i + 1,
+            metrics.accuracy * 100.0,
+            metrics.precision * 100.0,
+            metrics.recall * 100.0,
+            metrics.f1
+        );
+    }
+    println!(
+        "\n{}-fold cross-validation on {} emails (mean ± std dev):",
+        k,
+        emails.len()
+    );
+    println!("{}", MetricsSummary::from_metrics(&results));
+    Ok(())
+}
@@ -0,0 +1,400 @@
+use crate::classifier::SpamClassifier;
+use crate::data::k_folds;
+use crate::error::SpamError;
+use std::fmt;
+use std::path::Path;
+
+// Number of evenly spaced thresholds between 1.0 and 0.0 on a precision-recall curve,
+// not counting 0.0 itself
+const CURVE_STEPS: usize = 100;
+
+// Width in characters of the longest bar when a ScoreHistogram is displayed
+const HISTOGRAM_WIDTH: usize = 50;
+
+// Aggregate classification quality on a labeled test set, treating spam as the positive class
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub accuracy: f64,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+// Counts of correct and incorrect predictions, with spam as the positive class
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConfusionMatrix {
+    pub tp: usize,
+    pub tn: usize,
+    pub fp: usize,
+    pub fn_: usize,
+}
+
+impl ConfusionMatrix {
+    // Tally one prediction against its true label
+    pub fn record(&mut self, actual_spam: bool, predicted_spam: bool) {
+        match (actual_spam, predicted_spam) {
+            (true, true) => self.tp += 1,
+            (false, false) => self.tn += 1,
+            (false, true) => self.fp += 1,
+            (true, false) => self.fn_ += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.tp + self.tn + self.fp + self.fn_
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        let (tp, fp, fn_) = (self.tp, self.fp, self.fn_);
+        let accuracy = ratio(tp + self.tn, self.total());
+        let precision = ratio(tp, tp + fp);
+        let recall = ratio(tp, tp + fn_);
+        let f1 = if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
+        } else {
+            0.0
+        };
+
+        Metrics {
+            accuracy,
+            precision,
+            recall,
+            f1,
+        }
+    }
+}
+
+// Division that reports 0.0 instead of NaN when nothing was counted
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+impl fmt::Display for ConfusionMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<12} {:>15} {:>15}",
+            "", "Predicted Spam", "Predicted Ham"
+        )?;
+        writeln!(f, "{:<12} {:>15} {:>15}", "Actual Spam", self.tp, self.fn_)?;
+        write!(f, "{:<12} {:>15} {:>15}", "Actual Ham", self.fp, self.tn)
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Accuracy:  {:.1}%", self.accuracy * 100.0)?;
+        writeln!(f, "Precision: {:.1}%", self.precision * 100.0)?;
+        writeln!(f, "Recall:    {:.1}%", self.recall * 100.0)?;
+        write!(f, "F1 score:  {:.3}", self.f1)
+    }
+}
+
+// Number of messages whose spam probability falls in each of several equal-width
+// bins over [0.0, 1.0], lowest first. A score of exactly 1.0 counts towards the
+// last bin. Displayed as an ASCII bar chart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreHistogram {
+    pub counts: Vec<usize>,
+}
+
+impl ScoreHistogram {
+    // Tally scores into `bins` bins; at least one bin is always used
+    pub fn from_scores(scores: impl IntoIterator<Item = f64>, bins: usize) -> ScoreHistogram {
+        let bins = bins.max(1);
+        let mut counts = vec![0; bins];
+        for score in scores {
+            let bin = (score.clamp(0.0, 1.0) * bins as f64) as usize;
+            counts[bin.min(bins - 1)] += 1;
+        }
+        ScoreHistogram { counts }
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    // The (low, high) score range of a bin
+    pub fn bin_range(&self, bin: usize) -> (f64, f64) {
+        let width = 1.0 / self.counts.len() as f64;
+        (bin as f64 * width, (bin + 1) as f64 * width)
+    }
+}
+
+impl fmt::Display for ScoreHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let largest = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        for (bin, &count) in self.counts.iter().enumerate() {
+            let (low, high) = self.bin_range(bin);
+            // Round up so that any non-empty bin shows at least one mark
+            let bar = (count * HISTOGRAM_WIDTH).div_ceil(largest);
+            if bin > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:.2}-{:.2} | {} {}", low, high, "#".repeat(bar), count)?;
+        }
+        Ok(())
+    }
+}
+
+// Mean and standard deviation of each metric across several evaluations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetricsSummary {
+    pub mean: Metrics,
+    pub std_dev: Metrics,
+}
+
+impl MetricsSummary {
+    // Summarize a non-empty list of metrics using the population standard deviation
+    pub fn from_metrics(metrics: &[Metrics]) -> MetricsSummary {
+        let stat = |field: fn(&Metrics) -> f64| {
+            let n = metrics.len().max(1) as f64;
+            let mean = metrics.iter().map(field).sum::<f64>() / n;
+            let variance = metrics
+                .iter()
+                .map(|m| (field(m) - mean).powi(2))
+                .sum::<f64>()
+                / n;
+            (mean, variance.sqrt())
+        };
+        let (accuracy, accuracy_sd) = stat(|m| m.accuracy);
+        let (precision, precision_sd) = stat(|m| m.precision);
+        let (recall, recall_sd) = stat(|m| m.recall);
+        let (f1, f1_sd) = stat(|m| m.f1);
+
+        MetricsSummary {
+            mean: Metrics {
+                accuracy,
+                precision,
+                recall,
+                f1,
+            },
+            std_dev: Metrics {
+                accuracy: accuracy_sd,
+                precision: precision_sd,
+                recall: recall_sd,
+                f1: f1_sd,
+            },
+        }
+    }
+}
+
+impl fmt::Display for MetricsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (mean, sd) = (&self.mean, &self.std_dev);
+        writeln!(
+            f,
+            "Accuracy:  {:.1}% ± {:.1}",
+            mean.accuracy * 100.0,
+            sd.accuracy * 100.0
+        )?;
+        writeln!(
+            f,
+            "Precision: {:.1}% ± {:.1}",
+            mean.precision * 100.0,
+            sd.precision * 100.0
+        )?;
+        writeln!(
+            f,
+            "Recall:    {:.1}% ± {:.1}",
+            mean.recall * 100.0,
+            sd.recall * 100.0
+        )?;
+        write!(f, "F1 score:  {:.3} ± {:.3}", mean.f1, sd.f1)
+    }
+}
+
+// k-fold cross-validation: split the emails into k seeded folds, train a default
+// classifier on k-1 of them and evaluate it on the remaining one, once per fold.
+// Summarize the returned per-fold metrics with MetricsSummary::from_metrics.
+pub fn cross_validate(
+    emails: &[(String, String)],
+    k: usize,
+    seed: u64,
+) -> Result<Vec<Metrics>, SpamError> {
+    cross_validate_with(emails, k, seed, || Ok(SpamClassifier::new()))
+}
+
+// Like cross_validate, but each fold trains a fresh classifier from `new_classifier`
+pub fn cross_validate_with<F>(
+    emails: &[(String, String)],
+    k: usize,
+    seed: u64,
+    new_classifier: F,
+) -> Result<Vec<Metrics>, SpamError>
+where
+    F: Fn() -> Result<SpamClassifier, SpamError>,
+{
+    let folds = k_folds(emails, k, seed)?;
+
+    let mut results = Vec::with_capacity(k);
+    for (i, test) in folds.iter().enumerate() {
+        let train: Vec<(String, String)> = folds
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .flat_map(|(_, fold)| fold.iter().cloned())
+            .collect();
+
+        let mut classifier = new_classifier()?;
+        classifier.train(&train)?;
+        results.push(classifier.evaluate(test));
+    }
+    Ok(results)
+}
+
+// (threshold, precision, recall) for thresholds from 1.0 down to 0.0 over
+// (score, is_spam) pairs such as SpamClassifier::scored_labels returns. A message
+// counts as spam when its score is above the threshold, as in predict.
+pub fn precision_recall_curve(scored: &[(f64, bool)]) -> Vec<(f64, f64, f64)> {
+    (0..=CURVE_STEPS)
+        .rev()
+        .map(|step| {
+            let threshold = step as f64 / CURVE_STEPS as f64;
+            let mut matrix = ConfusionMatrix::default();
+            for &(score, actual_spam) in scored {
+                matrix.record(actual_spam, score > threshold);
+            }
+            let metrics = matrix.metrics();
+            (threshold, metrics.precision, metrics.recall)
+        })
+        .collect()
+}
+
+// Write a precision-recall curve as threshold,precision,recall CSV for plotting
+pub fn write_curve(path: &Path, curve: &[(f64, f64, f64)]) -> Result<(), SpamError> {
+    let mut wtr = csv::Writer::from_path(path)?;
+    wtr.write_record(["threshold", "precision", "recall"])?;
+    for (threshold, precision, recall) in curve {
+        wtr.write_record([
+            format!("{:.2}", threshold),
+            format!("{:.4}", precision),
+            format!("{:.4}", recall),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::split_dataset;
+
+    fn emails(rows: &[(&str, &str)]) -> Vec<(String, String)> {
+        rows.iter()
+            .map(|(label, content)| (label.to_string(), content.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn confusion_matrix_counts_each_outcome() {
+        let mut classifier = SpamClassifier::new();
+        classifier
+            .train(&emails(&[
+                ("spam", "win a free prize now"),
+                ("spam", "claim your free lottery prize"),
+                ("ham", "open the window before the meeting"),
+                ("ham", "the meeting notes are attached"),
+            ]))
+            .unwrap();
+
+        let test = emails(&[
+            ("spam", "free prize"),
+            ("spam", "claim a lottery prize"),
+            ("ham", "meeting notes"),
+            ("ham", "free lottery"),
+            ("spam", "meeting window"),
+        ]);
+        let matrix = classifier.confusion_matrix(&test);
+        assert_eq!(
+            matrix,
+            ConfusionMatrix {
+                tp: 2,
+                tn: 1,
+                fp: 1,
+                fn_: 1,
+            }
+        );
+
+        let grid = matrix.to_string();
+        let rows: Vec<&str> = grid.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].contains("Predicted Spam") && rows[0].contains("Predicted Ham"));
+        assert!(rows[1].starts_with("Actual Spam") && rows[1].ends_with(" 1"));
+        assert!(rows[2].starts_with("Actual Ham") && rows[2].ends_with(" 1"));
+    }
+
+    // Forty emails, half spam, each with a word of its own
+    fn numbered_emails() -> Vec<(String, String)> {
+        (0..40)
+            .map(|i| {
+                let label = if i % 2 == 0 { "spam" } else { "ham" };
+                let words = if i % 2 == 0 {
+                    "free prize"
+                } else {
+                    "meeting notes"
+                };
+                (label.to_string(), format!("{} item{}", words, i))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_split_and_metrics() {
+        let emails = numbered_emails();
+        let evaluate = |seed: u64| {
+            let (train, test) = split_dataset(&emails, 0.25, seed);
+            let mut classifier = SpamClassifier::new();
+            classifier.train(&train).unwrap();
+            (train, test.clone(), classifier.evaluate(&test))
+        };
+        assert_eq!(evaluate(7), evaluate(7));
+        assert_ne!(evaluate(7).1, evaluate(8).1);
+        assert_eq!(
+            cross_validate(&emails, 4, 7).unwrap(),
+            cross_validate(&emails, 4, 7).unwrap()
+        );
+    }
+
+    #[test]
+    fn recall_grows_as_the_threshold_falls() {
+        let mut classifier = SpamClassifier::new();
+        classifier.train(&numbered_emails()[..30]).unwrap();
+        let mut scored = classifier.scored_labels(&numbered_emails()[30..]);
+        scored.extend([(0.3, true), (0.7, false), (0.55, true)]);
+
+        let curve = precision_recall_curve(&scored);
+        assert_eq!(curve.len(), CURVE_STEPS + 1);
+        assert_eq!(curve[0].0, 1.0);
+        assert_eq!(curve[CURVE_STEPS].0, 0.0);
+        for pair in curve.windows(2) {
+            assert!(pair[1].0 < pair[0].0);
+            assert!(pair[1].2 >= pair[0].2, "{:?}", pair);
+        }
+        assert_eq!(curve[CURVE_STEPS].2, 1.0);
+    }
+
+    #[test]
+    fn histogram_bins_add_up_to_the_scored_emails() {
+        let mut classifier = SpamClassifier::new();
+        classifier.train(&numbered_emails()[..30]).unwrap();
+        let emails = numbered_emails();
+        for bins in [1, 4, 10, 33] {
+            let histogram = classifier.score_histogram(&emails, bins);
+            assert_eq!(histogram.counts.len(), bins);
+            assert_eq!(histogram.total(), emails.len());
+        }
+
+        // Scores of exactly 0.0 and 1.0 land in the first and last bins
+        let histogram = ScoreHistogram::from_scores([0.0, 0.25, 0.5, 1.0], 4);
+        assert_eq!(histogram.counts, vec![1, 1, 1, 1]);
+        assert_eq!(histogram.bin_range(3), (0.75, 1.0));
+        assert_eq!(ScoreHistogram::from_scores([0.5], 0).counts, vec![1]);
+        assert_eq!(histogram.to_string().lines().count(), 4);
+    }
+}
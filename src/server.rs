@@ -0,0 +1,77 @@
+// A local HTTP server for classifying messages from other services. It answers
+// POST /classify with {"message": "..."} by {"label": ..., "score": ...}, and
+// GET /health with {"status": "ok"}.
+use crate::classifier::SpamClassifier;
+use crate::error::SpamError;
+use serde::Deserialize;
+use serde_json::json;
+use std::io::{self, Read};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// Request bodies larger than this are rejected with 413
+const MAX_BODY_BYTES: u64 = 10 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct ClassifyRequest {
+    message: String,
+}
+
+// Listen on `addr` (e.g. "127.0.0.1:8080") and answer requests one at a time
+// until the process is stopped. Fails only when the address cannot be bound.
+pub fn serve(classifier: &SpamClassifier, addr: &str) -> Result<(), SpamError> {
+    let server = Server::http(addr)
+        .map_err(|e| io::Error::other(format!("cannot listen on {}: {}", addr, e)))?;
+    log::info!("Listening on http://{}", addr);
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = respond(classifier, &mut request);
+        let header =
+            Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header);
+        if let Err(e) = request.respond(response) {
+            log::warn!("Failed to send response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+// Status code and JSON body for a request
+fn respond(classifier: &SpamClassifier, request: &mut Request) -> (u16, serde_json::Value) {
+    let route = request.url().split('?').next().unwrap_or("");
+    match (request.method(), route) {
+        (Method::Get, "/health") => (200, json!({ "status": "ok" })),
+        (Method::Post, "/classify") => classify(classifier, request),
+        (_, "/health" | "/classify") => (405, json!({ "error": "method not allowed" })),
+        _ => (404, json!({ "error": "not found" })),
+    }
+}
+
+fn classify(classifier: &SpamClassifier, request: &mut Request) -> (u16, serde_json::Value) {
+    let mut body = Vec::new();
+    let mut reader = request.as_reader().take(MAX_BODY_BYTES + 1);
+    if let Err(e) = reader.read_to_end(&mut body) {
+        return (
+            400,
+            json!({ "error": format!("cannot read request body: {}", e) }),
+        );
+    }
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return (413, json!({ "error": "request body too large" }));
+    }
+
+    match serde_json::from_slice::<ClassifyRequest>(&body) {
+        Ok(ClassifyRequest { message }) => {
+            let prediction = classifier.classify(&message);
+            (
+                200,
+                json!({ "label": prediction.label, "score": prediction.score }),
+            )
+        }
+        Err(e) => (
+            400,
+            json!({ "error": format!("expected {{\"message\": \"...\"}}: {}", e) }),
+        ),
+    }
+}
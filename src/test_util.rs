@@ -0,0 +1,13 @@
+// Helpers shared by the unit tests of every module
+use std::fs;
+use std::path::PathBuf;
+
+// An empty directory for one test, so tests running in parallel never share files
+pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("spamdetect-test-{}", std::process::id()))
+        .join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,617 @@
+use crate::error::SpamError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Characters that carry spam signal on their own and are kept as tokens
+const SYMBOL_CHARS: [char; 1] = ['$'];
+
+// Text emoticons kept as tokens when they end a whitespace-separated chunk.
+// Longer forms come first so ":-)" is not read as "-" followed by ":)".
+pub const EMOTICONS: [&str; 16] = [
+    ":-)", ":-(", ":-D", ":-P", ":-p", ":-O", ";-)", ":'(", ":)", ":(", ":D", ":P", ":p", ":O",
+    ";)", "<3",
+];
+
+// Split text into lowercase word tokens shared by training and prediction.
+// Words follow Unicode word boundaries, so accented words stay whole and each
+// CJK ideograph is its own token. Punctuation is dropped, apostrophes inside
+// words are kept so contractions like "you've" stay whole, and runs of symbols
+// such as "$$$" become tokens. Each emoji (including flags and ZWJ sequences)
+// and each emoticon from EMOTICONS is a token of its own.
+pub fn tokenize(text: &str) -> Vec<String> {
+    split_words(text, false)
+}
+
+// Tokenize like `tokenize` but keep the original case, so "FREE" and "free"
+// are different tokens
+pub fn tokenize_case_sensitive(text: &str) -> Vec<String> {
+    split_words(text, true)
+}
+
+fn split_words(text: &str, case_sensitive: bool) -> Vec<String> {
+    let mut tokens = Vec::new();
+    // Reused across chunks so long messages do not allocate a buffer per word
+    let mut symbols = String::new();
+    let mut word = String::new();
+
+    for chunk in text.split_whitespace() {
+        let (rest, emoticon) = split_emoticon(chunk);
+
+        for segment in rest.split_word_bounds() {
+            if segment.chars().any(is_emoji) {
+                flush(&mut symbols, &mut tokens);
+                // Drop variation selectors so "❤️" and "❤" are the same token
+                tokens.push(segment.replace('\u{fe0f}', ""));
+            } else if segment.chars().any(char::is_alphanumeric) {
+                flush(&mut symbols, &mut tokens);
+                if case_sensitive || segment.is_ascii() {
+                    word.push_str(segment);
+                    if !case_sensitive {
+                        word.make_ascii_lowercase();
+                    }
+                } else {
+                    word.push_str(&segment.to_lowercase());
+                }
+                if word.contains('’') {
+                    word = word.replace('’', "'");
+                }
+                flush(&mut word, &mut tokens);
+            } else if segment.chars().all(|c| SYMBOL_CHARS.contains(&c)) {
+                symbols.push_str(segment);
+            } else {
+                flush(&mut symbols, &mut tokens);
+            }
+        }
+
+        flush(&mut symbols, &mut tokens);
+        if let Some(emoticon) = emoticon {
+            tokens.push(emoticon.to_string());
+        }
+    }
+    tokens
+}
+
+// Separate a trailing emoticon, as in "thanks:)", from the rest of a chunk
+fn split_emoticon(chunk: &str) -> (&str, Option<&'static str>) {
+    match EMOTICONS.iter().find(|emoticon| chunk.ends_with(*emoticon)) {
+        Some(emoticon) => (&chunk[..chunk.len() - emoticon.len()], Some(*emoticon)),
+        None => (chunk, None),
+    }
+}
+
+// Pictographic characters: the emoji blocks, plus the miscellaneous symbols and
+// dingbats that are commonly rendered as emoji (☀, ✅, ❤)
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B50 | 0x2B55)
+}
+
+// Move a finished token into the output, dropping any trailing apostrophes
+fn flush(buffer: &mut String, tokens: &mut Vec<String>) {
+    let token = buffer.trim_end_matches('\'');
+    if !token.is_empty() {
+        tokens.push(token.to_string());
+    }
+    buffer.clear();
+}
+
+// Token that replaces every detected URL
+pub const URL_TOKEN: &str = "__URL__";
+
+// Top-level domains recognized in bare domains such as "example.com"
+const URL_TLDS: [&str; 24] = [
+    "com", "net", "org", "info", "biz", "io", "co", "us", "uk", "de", "ru", "cn", "in", "xyz",
+    "top", "online", "site", "club", "me", "ly", "gl", "tk", "ws", "cc",
+];
+
+// What detected URLs turn into during tokenization
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UrlFeatures {
+    // URLs are tokenized like any other text
+    #[default]
+    Off,
+    // Each URL becomes a single URL_TOKEN, so counts of URL_TOKEN per class
+    // let the number of links in a message act as a learnable feature
+    Token,
+    // Each URL becomes URL_TOKEN followed by its lowercase domain, e.g. "example.com"
+    TokenAndDomain,
+}
+
+// Tokenize text like `tokenize` (or `tokenize_case_sensitive`) but replace URLs
+// according to `features`. Domains are always lowercase.
+pub fn tokenize_with_urls(text: &str, features: UrlFeatures, case_sensitive: bool) -> Vec<String> {
+    if features == UrlFeatures::Off {
+        return split_words(text, case_sensitive);
+    }
+
+    let mut tokens = Vec::new();
+    for chunk in text.split_whitespace() {
+        match url_domain(chunk) {
+            Some(domain) => {
+                tokens.push(URL_TOKEN.to_string());
+                if features == UrlFeatures::TokenAndDomain {
+                    tokens.push(domain);
+                }
+            }
+            None => tokens.extend(split_words(chunk, case_sensitive)),
+        }
+    }
+    tokens
+}
+
+// The domain of a whitespace-free chunk that looks like a URL: anything starting
+// with http://, https:// or www., or a bare domain with a known top-level domain
+fn url_domain(chunk: &str) -> Option<String> {
+    let chunk = chunk.trim_matches(|c: char| "()[]<>\"',.;:!?".contains(c));
+    let lower = chunk.to_lowercase();
+
+    let (rest, explicit) = if let Some(rest) = lower.strip_prefix("https://") {
+        (rest, true)
+    } else if let Some(rest) = lower.strip_prefix("http://") {
+        (rest, true)
+    } else {
+        (lower.as_str(), lower.starts_with("www."))
+    };
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.split(':').next().unwrap_or("");
+    let host = host.strip_prefix("www.").unwrap_or(host);
+
+    let labels: Vec<&str> = host.split('.').collect();
+    let valid_labels = labels.len() >= 2
+        && labels
+            .iter()
+            .all(|l| !l.is_empty() && l.chars().all(|c| c.is_alphanumeric() || c == '-'));
+    if !valid_labels || host.contains('@') {
+        return None;
+    }
+
+    let tld = labels[labels.len() - 1];
+    if explicit || URL_TLDS.contains(&tld) {
+        Some(host.to_string())
+    } else {
+        None
+    }
+}
+
+// Structural tokens describing how a message is written rather than what it says
+pub const CAPS_HIGH_TOKEN: &str = "__CAPS_HIGH__";
+pub const CAPS_SOME_TOKEN: &str = "__CAPS_SOME__";
+pub const EXCLAMATION_TOKEN: &str = "__EXCLAIM__";
+
+// Exclamation marks beyond this many add no further exclamation tokens
+const MAX_EXCLAMATION_TOKENS: usize = 5;
+
+// Shouting features of a message: a caps token when at least half (CAPS_HIGH_TOKEN)
+// or a fifth (CAPS_SOME_TOKEN) of its letters are uppercase, plus one
+// EXCLAMATION_TOKEN per exclamation mark up to MAX_EXCLAMATION_TOKENS
+pub fn structural_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+
+    let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+    let uppercase = text.chars().filter(|c| c.is_uppercase()).count();
+    if letters > 0 {
+        let caps_ratio = uppercase as f64 / letters as f64;
+        if caps_ratio >= 0.5 {
+            tokens.push(CAPS_HIGH_TOKEN.to_string());
+        } else if caps_ratio >= 0.2 {
+            tokens.push(CAPS_SOME_TOKEN.to_string());
+        }
+    }
+
+    let exclamations = text.chars().filter(|&c| c == '!').count();
+    for _ in 0..exclamations.min(MAX_EXCLAMATION_TOKENS) {
+        tokens.push(EXCLAMATION_TOKEN.to_string());
+    }
+
+    tokens
+}
+
+// Remove HTML tags (and the contents of script and style blocks) and decode the
+// common entities, leaving only the visible text. Tags are replaced by a space so
+// words on either side of them are not glued together.
+pub fn strip_html(text: &str) -> String {
+    let mut visible = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        visible.push_str(&rest[..start]);
+        let tag_and_rest = &rest[start..];
+        let Some(end) = tag_and_rest.find('>') else {
+            // A lone '<' is text, not a tag
+            visible.push_str(tag_and_rest);
+            rest = "";
+            break;
+        };
+
+        let tag = tag_and_rest[1..end].to_ascii_lowercase();
+        let tag_name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("");
+        rest = &tag_and_rest[end + 1..];
+
+        if tag_name == "script" || tag_name == "style" {
+            let closing = format!("</{}", tag_name);
+            rest = match find_ignore_ascii_case(rest, &closing) {
+                Some(close) => &rest[close..],
+                None => "",
+            };
+        }
+        visible.push(' ');
+    }
+    visible.push_str(rest);
+
+    // &amp; goes last so "&amp;lt;" decodes to "&lt;" rather than "<"
+    visible
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// Byte offset of the first ASCII case-insensitive match of `needle`, found
+// without lowercasing a copy of the haystack
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    let needle = needle.as_bytes();
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+// Joins the words of an n-gram feature, e.g. "click here"
+pub const NGRAM_SEPARATOR: &str = " ";
+
+// Extend unigram tokens with every run of 2..=n adjacent words joined by NGRAM_SEPARATOR
+pub fn add_ngrams(tokens: Vec<String>, n: usize) -> Vec<String> {
+    if n <= 1 {
+        return tokens;
+    }
+
+    let mut features = tokens.clone();
+    for size in 2..=n {
+        features.extend(tokens.windows(size).map(|w| w.join(NGRAM_SEPARATOR)));
+    }
+    features
+}
+
+// Shorten every run of three or more identical letters to two, so "freeeee"
+// becomes "free" and "winnnner" becomes "winner". Digits and symbols are kept as is.
+pub fn collapse_repeats(word: &str) -> String {
+    let mut collapsed = String::with_capacity(word.len());
+    let mut previous = None;
+    let mut run = 0;
+    for c in word.chars() {
+        run = if Some(c) == previous { run + 1 } else { 1 };
+        previous = Some(c);
+        if run <= 2 || !c.is_alphabetic() {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+// Tokens that replace numbers and money amounts when numbers are normalized
+pub const NUM_TOKEN: &str = "__NUM__";
+pub const MONEY_TOKEN: &str = "__MONEY__";
+
+// Replace every numeric token (digits with optional ',' and '.' separators, such
+// as "5000", "10,000" or "1.5") by NUM_TOKEN, so specific values stop being
+// features of their own. A number next to a currency token ("$") becomes a single
+// MONEY_TOKEN, so "$10,000" and "$5000" are the same feature.
+pub fn normalize_numbers(tokens: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tokens.len());
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        if is_currency(&token) && tokens.peek().is_some_and(|next| is_number(next)) {
+            tokens.next();
+            normalized.push(MONEY_TOKEN.to_string());
+        } else if is_number(&token) {
+            if tokens.peek().is_some_and(|next| is_currency(next)) {
+                tokens.next();
+                normalized.push(MONEY_TOKEN.to_string());
+            } else {
+                normalized.push(NUM_TOKEN.to_string());
+            }
+        } else {
+            normalized.push(token);
+        }
+    }
+    normalized
+}
+
+fn is_number(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_digit())
+        && token
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == ',' || c == '.')
+}
+
+fn is_currency(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| SYMBOL_CHARS.contains(&c))
+}
+
+// Whether `phrase` occurs in `text` on word boundaries: an end of the phrase that
+// is a letter or digit must not touch another letter or digit in the text, so
+// "win" is found in "you win!" but not in "window". Symbol phrases such as "$$$"
+// match anywhere.
+pub(crate) fn contains_phrase(text: &str, phrase: &str) -> bool {
+    let is_word_char = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let starts_word = is_word_char(phrase.chars().next());
+    let ends_word = is_word_char(phrase.chars().next_back());
+    text.match_indices(phrase).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + phrase.len()..].chars().next();
+        let joined_before = starts_word && is_word_char(before);
+        let joined_after = ends_word && is_word_char(after);
+        !(joined_before || joined_after)
+    })
+}
+
+// Prefix of phonetic codes, which keeps them apart from word tokens
+pub const PHONETIC_PREFIX: &str = "~";
+
+// The Soundex code of a word, prefixed with PHONETIC_PREFIX: its first letter
+// followed by three digits for the consonant sounds after it, so words that sound
+// alike share a code ("medicine" and "medecine" both give "~m325", "loan" and
+// "lone" both "~l500"). None for tokens that are not purely ASCII letters, such as
+// numbers, symbols or special tokens, which keep their own spelling.
+pub fn soundex(word: &str) -> Option<String> {
+    if word.is_empty() || !word.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let mut letters = word.chars().map(|c| c.to_ascii_lowercase());
+    let first = letters.next()?;
+    let mut code = format!("{}{}", PHONETIC_PREFIX, first);
+    let mut digits = 0;
+    let mut previous = soundex_digit(first);
+    for c in letters {
+        let digit = soundex_digit(c);
+        if let Some(d) = digit.filter(|_| digit != previous) {
+            code.push(d);
+            digits += 1;
+            if digits == 3 {
+                break;
+            }
+        }
+        // 'h' and 'w' do not separate two consonants with the same digit, vowels do
+        if c != 'h' && c != 'w' {
+            previous = digit;
+        }
+    }
+    for _ in digits..3 {
+        code.push('0');
+    }
+    Some(code)
+}
+
+// The Soundex digit of a consonant; None for vowels, 'y', 'h' and 'w'
+fn soundex_digit(c: char) -> Option<char> {
+    match c {
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => None,
+    }
+}
+
+// Prefix of character n-gram features, which keeps them apart from word tokens
+pub const CHAR_NGRAM_PREFIX: &str = "#";
+
+// Character n-grams of length n over each word, padded with '<' and '>' so word
+// starts and ends get grams of their own, e.g. "free" gives "#<fr", "#fre",
+// "#ree" and "#ee>" for n = 3. Runs of single-character words are joined first,
+// so spaced-out text like "f r e e" yields the same grams as "free". Special
+// tokens such as URL_TOKEN are skipped.
+pub fn char_ngrams(words: &[String], n: usize) -> Vec<String> {
+    let mut grams = Vec::new();
+    if n == 0 {
+        return grams;
+    }
+
+    let mut joined: Vec<String> = Vec::new();
+    let mut spaced = String::new();
+    for word in words.iter().filter(|word| !word.starts_with("__")) {
+        if word.chars().count() == 1 {
+            spaced.push_str(word);
+            continue;
+        }
+        if !spaced.is_empty() {
+            joined.push(std::mem::take(&mut spaced));
+        }
+        joined.push(word.clone());
+    }
+    if !spaced.is_empty() {
+        joined.push(spaced);
+    }
+
+    for word in joined {
+        let padded: Vec<char> = format!("<{}>", word).chars().collect();
+        for window in padded.windows(n) {
+            let gram: String = window.iter().collect();
+            grams.push(format!("{}{}", CHAR_NGRAM_PREFIX, gram));
+        }
+    }
+    grams
+}
+
+// Small list of common English words that carry no spam signal
+pub const DEFAULT_STOP_WORDS: [&str; 32] = [
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "has", "have", "i", "in", "is",
+    "it", "its", "me", "my", "of", "on", "or", "our", "that", "the", "this", "to", "was", "we",
+    "will", "with", "you",
+];
+
+pub fn default_stop_words() -> HashSet<String> {
+    DEFAULT_STOP_WORDS.iter().map(|w| w.to_string()).collect()
+}
+
+// Load a custom stop-word list from a text file with one word per line
+pub fn load_stop_words(path: &Path) -> Result<HashSet<String>, SpamError> {
+    let contents = fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    #[test]
+    fn phrases_match_on_word_boundaries() {
+        assert!(contains_phrase("you win!", "win"));
+        assert!(contains_phrase("click here now", "click here"));
+        assert!(!contains_phrase("open the window", "win"));
+        assert!(!contains_phrase("a twin", "win"));
+        assert!(!contains_phrase("freedom", "free"));
+        assert!(contains_phrase("win$$$now", "$$$"));
+        assert!(contains_phrase("window, then win", "win"));
+    }
+
+    #[test]
+    fn tokenize_strips_punctuation_case_and_extra_spaces() {
+        assert_eq!(tokenize("FREE! free, Free."), ["free", "free", "free"]);
+        assert_eq!(tokenize("  win   big \t now\n"), ["win", "big", "now"]);
+        assert_eq!(tokenize("Click-here (now)"), ["click", "here", "now"]);
+        assert_eq!(
+            tokenize("You've won $$$, claim it"),
+            ["you've", "won", "$$$", "claim", "it"]
+        );
+        assert_eq!(tokenize("$$$,"), tokenize("$$$"));
+        assert!(tokenize(" ... !!! ").is_empty());
+    }
+
+    #[test]
+    fn stop_words_load_one_per_line() {
+        let dir = scratch_dir("stop-words");
+        let path = dir.join("stop-words.txt");
+        fs::write(&path, "The\n  and \n\nof\n").unwrap();
+
+        let words = load_stop_words(&path).unwrap();
+        let expected: HashSet<String> = ["the", "and", "of"].map(String::from).into();
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn strip_html_leaves_only_visible_text() {
+        let html = "<div class=\"promo\"><a href=\"http://x.io\">Click&nbsp;here</a></div>\
+                    <style>p { color: red }</style><p>Tom &amp; Jerry</p>";
+        assert_eq!(
+            tokenize(&strip_html(html)),
+            ["click", "here", "tom", "jerry"]
+        );
+        assert_eq!(strip_html("1 < 2"), "1 < 2");
+    }
+
+    #[test]
+    fn urls_become_tokens() {
+        let urls = |text: &str| {
+            tokenize_with_urls(text, UrlFeatures::Token, false)
+                .iter()
+                .filter(|token| *token == URL_TOKEN)
+                .count()
+        };
+        assert_eq!(urls("no links here, see you at 5.30"), 0);
+        assert_eq!(urls("Visit https://Deals.example.com/offer?id=1 now"), 1);
+        assert_eq!(
+            urls("www.a.net, http://b.org and (c.xyz) or mail me@d.com"),
+            3
+        );
+
+        assert_eq!(
+            tokenize_with_urls(
+                "Go to https://WWW.Example.com/x!",
+                UrlFeatures::TokenAndDomain,
+                false
+            ),
+            ["go", "to", URL_TOKEN, "example.com"]
+        );
+        assert_eq!(
+            tokenize_with_urls("see example.com", UrlFeatures::Off, false),
+            ["see", "example.com"]
+        );
+    }
+
+    #[test]
+    fn structural_tokens_count_caps_and_exclamations() {
+        assert_eq!(
+            structural_tokens("FREE MONEY!!!"),
+            [
+                CAPS_HIGH_TOKEN,
+                EXCLAMATION_TOKEN,
+                EXCLAMATION_TOKEN,
+                EXCLAMATION_TOKEN
+            ]
+        );
+        assert_eq!(structural_tokens("Hi Bob, See You"), [CAPS_SOME_TOKEN]);
+        assert!(structural_tokens("see you soon.").is_empty());
+        assert_eq!(
+            structural_tokens(&"!".repeat(20)).len(),
+            MAX_EXCLAMATION_TOKENS
+        );
+    }
+
+    #[test]
+    fn tokenize_handles_accents_and_cjk() {
+        assert_eq!(
+            tokenize("GRÜSSE aus München! ¿Qué tal, señor?"),
+            ["grüsse", "aus", "münchen", "qué", "tal", "señor"]
+        );
+        assert_eq!(
+            tokenize("Ça coûte très CHER"),
+            ["ça", "coûte", "très", "cher"]
+        );
+        assert_eq!(tokenize("免费礼品 now"), ["免", "费", "礼", "品", "now"]);
+    }
+
+    #[test]
+    fn emoji_and_emoticons_are_tokens() {
+        assert_eq!(tokenize("gift🎁💰 now!"), ["gift", "🎁", "💰", "now"]);
+        assert_eq!(tokenize("love it ❤️"), tokenize("love it ❤"));
+        assert_eq!(
+            tokenize("thanks:) see you :-D"),
+            ["thanks", ":)", "see", "you", ":-D"]
+        );
+    }
+
+    #[test]
+    fn collapse_repeats_shortens_letter_runs() {
+        assert_eq!(collapse_repeats("winnnner"), collapse_repeats("winner"));
+        assert_eq!(collapse_repeats("winnnner"), "winner");
+        assert_eq!(collapse_repeats("freeeee"), "free");
+        assert_eq!(collapse_repeats("wiiiin"), "wiin");
+        assert_eq!(collapse_repeats("1000000"), "1000000");
+    }
+
+    #[test]
+    fn money_amounts_collapse_to_one_token() {
+        for text in ["$10,000", "$5000", "$1.5", "20$"] {
+            assert_eq!(
+                normalize_numbers(tokenize(text)),
+                vec![MONEY_TOKEN],
+                "{:?}",
+                text
+            );
+        }
+        assert_eq!(
+            normalize_numbers(tokenize("call 555 1234 now")),
+            vec!["call", NUM_TOKEN, NUM_TOKEN, "now"]
+        );
+        // Without normalization each amount is a feature of its own
+        assert_ne!(tokenize("$10,000"), tokenize("$5000"));
+    }
+}
@@ -0,0 +1,55 @@
+// JavaScript bindings for running the classifier in the browser. Build them as
+// a cdylib with `cargo rustc --lib --crate-type cdylib --target
+// wasm32-unknown-unknown --no-default-features --features wasm` and run
+// wasm-bindgen on the result (see the README); nothing here touches the
+// filesystem, so models are trained from CSV text held in memory.
+use crate::classifier::SpamClassifier;
+use crate::error::SpamError;
+use wasm_bindgen::prelude::*;
+
+// SpamClassifier as seen from JavaScript
+#[wasm_bindgen(js_name = SpamClassifier)]
+pub struct WasmClassifier {
+    inner: SpamClassifier,
+}
+
+#[wasm_bindgen(js_class = SpamClassifier)]
+impl WasmClassifier {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmClassifier {
+        WasmClassifier {
+            inner: SpamClassifier::new(),
+        }
+    }
+
+    // Train on a CSV dataset with label,content columns, given as a string
+    #[wasm_bindgen(js_name = trainFromStr)]
+    pub fn train_from_str(&mut self, csv: &str) -> Result<(), JsError> {
+        Ok(self.inner.train_from_csv_str(csv)?)
+    }
+
+    // Throws for an untrained model rather than trapping on its assertion
+    pub fn predict(&self, message: &str) -> Result<bool, JsError> {
+        if !self.inner.is_trained() {
+            return Err(SpamError::Untrained.into());
+        }
+        Ok(self.inner.predict(message))
+    }
+
+    #[wasm_bindgen(js_name = predictScore)]
+    pub fn predict_score(&self, message: &str) -> f64 {
+        self.inner.predict_score(message)
+    }
+
+    // The Prediction for a message as JSON, e.g.
+    // {"is_spam":true,"score":0.87,"label":"spam","top_features":[["free",2.1]]}
+    pub fn classify(&self, message: &str) -> String {
+        serde_json::to_string(&self.inner.classify(message)).expect("prediction serializes to JSON")
+    }
+}
+
+impl Default for WasmClassifier {
+    fn default() -> Self {
+        WasmClassifier::new()
+    }
+}
@@ -0,0 +1,267 @@
+// End-to-end tests of the spamdetect binary, each run in its own scratch directory
+use emailspamdetection::create_default_dataset;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// An empty directory for one test, so tests running in parallel never share files
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("spamdetect-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Run spamdetect in `dir` with `stdin` piped in and collect its output
+fn run(dir: &PathBuf, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_spamdetect"))
+        .args(args)
+        .current_dir(dir)
+        .env("RUST_LOG", "warn")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A run that fails early may exit before reading its input
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn retraining_keeps_keywords_added_at_runtime() {
+    let dir = scratch_dir("keywords");
+    assert!(run(&dir, &["keywords", "add", "tickets"], "")
+        .status
+        .success());
+    assert!(run(&dir, &["train"], "").status.success());
+    let listed = run(&dir, &["keywords"], "");
+    assert_eq!(stdout(&listed).trim(), "tickets");
+}
+
+#[test]
+fn train_progress_reaches_redirected_stderr() {
+    let dir = scratch_dir("progress");
+    create_default_dataset(dir.join("emails.csv")).unwrap();
+    let output = run(&dir, &["train", "--progress"], "");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Trained 20/20 emails"), "{}", stderr);
+}
+
+#[test]
+fn corrections_kept_out_of_the_dataset_do_not_make_the_model_stale() {
+    let dir = scratch_dir("corrections");
+    // Correct one message without adding it to the file, then one with
+    let session = "1\nhello there friend\nn\nn\n1\nlunch at noon tomorrow\nn\ny\n2\n";
+    assert!(run(&dir, &[], session).status.success());
+
+    let output = run(&dir, &[], "2\n");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        !stderr.contains("changed since the model was trained"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn end_of_input_exits_cleanly() {
+    let dir = scratch_dir("eof");
+    // Ctrl-D at the menu, then at the message prompt
+    for stdin in ["", "1\n"] {
+        let output = run(&dir, &[], stdin);
+        assert!(output.status.success());
+        assert!(stdout(&output).contains("Goodbye!"));
+        assert!(!stdout(&output).contains("Invalid choice"));
+    }
+}
+
+// Read `output` until it ends with `text` and return what was read, or None if
+// the text has not arrived after a few seconds
+fn wait_for(output: &mpsc::Receiver<u8>, text: &str) -> Option<String> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut seen = Vec::new();
+    while !seen.ends_with(text.as_bytes()) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        seen.push(output.recv_timeout(left).ok()?);
+    }
+    Some(String::from_utf8_lossy(&seen).into_owned())
+}
+
+#[test]
+fn prompts_appear_before_input_is_read() {
+    let dir = scratch_dir("prompts");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_spamdetect"))
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let (sender, output) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = [0; 256];
+        while let Ok(read @ 1..) = stdout.read(&mut buffer) {
+            for &byte in &buffer[..read] {
+                if sender.send(byte).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    // Each prompt has no trailing newline, so it only arrives if it was flushed
+    for (prompt, answer) in [
+        ("Enter your choice (1-9): ", "1\n"),
+        (
+            "Enter the email message to check for spam: ",
+            "free prize\n",
+        ),
+        ("Was this correct? (y/n): ", "y\n"),
+        ("Enter your choice (1-9): ", "2\n"),
+    ] {
+        assert!(
+            wait_for(&output, prompt).is_some(),
+            "{:?} never shown",
+            prompt
+        );
+        stdin.write_all(answer.as_bytes()).unwrap();
+    }
+    assert!(child.wait().unwrap().success());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn serve_classifies_posted_messages() {
+    use std::net::{TcpListener, TcpStream};
+
+    let dir = scratch_dir("serve");
+    // A port the system just handed out is almost certainly still free
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut server = Command::new(env!("CARGO_BIN_EXE_spamdetect"))
+        .args(["serve", "--port", &port.to_string()])
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let addr = format!("127.0.0.1:{}", port);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut stream = loop {
+        match TcpStream::connect(&addr) {
+            Ok(stream) => break stream,
+            Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+            Err(e) => panic!("server never listened on {}: {}", addr, e),
+        }
+    };
+    let body =
+        r#"{"message": "Congratulations! You've won a free iPhone! Claim your prize now!!!"}"#;
+    write!(
+        stream,
+        "POST /classify HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        addr,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains(r#""label":"spam""#), "{}", response);
+    assert!(response.contains(r#""score":"#), "{}", response);
+}
+
+#[test]
+fn no_autocreate_fails_on_a_missing_dataset() {
+    let dir = scratch_dir("no-autocreate");
+    let output = run(&dir, &["--no-autocreate", "--data", "missing.csv"], "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("missing.csv does not exist"), "{}", stderr);
+    assert!(!dir.join("missing.csv").exists());
+    assert!(!dir.join("model.json").exists());
+}
+
+#[test]
+fn classify_prints_stats_only_when_asked() {
+    let dir = scratch_dir("stats");
+    fs::write(
+        dir.join("inbox.csv"),
+        "content\nWin a free prize now\nThe meeting notes are attached\n",
+    )
+    .unwrap();
+    let classify = ["classify", "--input", "inbox.csv", "--output", "out.csv"];
+
+    let plain = run(&dir, &classify, "");
+    assert!(plain.status.success());
+    assert!(!stdout(&plain).contains("messages/s"), "{}", stdout(&plain));
+
+    let with_stats = run(&dir, &[&classify[..], &["--stats"]].concat(), "");
+    assert!(with_stats.status.success());
+    assert!(
+        stdout(&with_stats).contains("2 messages in "),
+        "{}",
+        stdout(&with_stats)
+    );
+    assert!(stdout(&with_stats).contains("messages/s)"));
+}
+
+#[test]
+fn check_warns_about_a_foreign_message() {
+    let dir = scratch_dir("language");
+    let french =
+        "Bonjour, pouvez-vous m'envoyer le rapport trimestriel avant la réunion de demain matin ?";
+    let output = run(&dir, &["check", french], "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Message looks like fra"), "{}", stderr);
+
+    let english = "Could you send me the quarterly report before tomorrow morning's meeting?";
+    let output = run(&dir, &["check", english], "");
+    assert!(String::from_utf8_lossy(&output.stderr).is_empty());
+}
+
+#[test]
+fn a_dataset_without_emails_is_replaced_or_rejected() {
+    for (name, contents) in [("header-only", "label,content\n"), ("zero-byte", "")] {
+        let dir = scratch_dir(name);
+        let data = dir.join("emails.csv");
+
+        fs::write(&data, contents).unwrap();
+        let output = run(&dir, &["train"], "");
+        assert!(!output.status.success(), "{}", name);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("contains no emails"), "{}", stderr);
+        assert_eq!(fs::read_to_string(&data).unwrap(), contents);
+
+        let output = run(&dir, &[], "2\n");
+        assert!(output.status.success(), "{}", name);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("replacing it with the default dataset"),
+            "{}",
+            stderr
+        );
+        assert!(fs::read_to_string(&data).unwrap().lines().count() > 1);
+    }
+}